@@ -0,0 +1,838 @@
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use echcell::csv::{
+    CSV,
+    Workbook,
+    Diagnostic,
+    OutputFormat,
+    Overwrite,
+    SpreadsheetFormat,
+    MAX_STREAM_WINDOW,
+    check,
+    check_strict,
+    generate_outputs,
+    get_json_summary,
+    get_output_path,
+    get_stream_window,
+    write_atomic,
+    write_output_as,
+    write_output_dual,
+    write_output_streaming_as,
+    write_spreadsheet,
+};
+use echcell::error::CsvError;
+use crate::{repl, tui, watch};
+
+/// Name given to sheets read from the standard input (linked files are resolved relative to the working directory).
+const STDIN_NAME: &str = "stdin.csv";
+
+const USAGE: &str = "Usage: echcell <command> [options]
+
+Commands:
+    eval <file> [-o <output>] [--format <format>] [--sheet <name>] [--keep-formulas] [--threads <n>] [--stream] [--strict]
+         [--diagnostics=<human|json>] [--no-clobber | --backup]
+                                                          evaluates a sheet and writes the output file
+    check <file>... [--diagnostics=<human|json>]          checks every formula without writing output files
+    get <file> <cell> [--raw]                             prints the value (or raw content) of a cell
+    explain <file> <cell>                                 shows how the value of a cell is evaluated
+    precedents <file> <cell> [--direct]                   lists the cells a cell reads (directly or through other cells)
+    dependents <file> <cell> [--direct]                   lists the cells that read a cell (directly or through other cells)
+    graph <file> [--format=<dot|json>] [-o <output>]      prints the dependency graph of the sheet
+    set <file> <cell> <item> [-o <output>] [--no-clobber | --backup]
+                                                          overwrites a cell and saves the sheet
+    convert <file> -o <output> [--format <format>] [--sheet <name>] [--keep-formulas] [--strict] [--no-clobber | --backup]
+                                                          writes the evaluated sheet to the given output
+    refresh <file> [-o <output>] [--no-clobber | --backup]
+                                                          evaluates an output written with --keep-formulas again
+    import <file> [--sheet <name>] [-o <output>] [--no-clobber | --backup]
+                                                          turns a sheet of an xlsx or ods file into an echcell sheet
+    fill <file> <source> <start> <end>                    copies a cell into a range and saves the sheet
+    workbook <directory or manifest>                      evaluates several sheets together
+    repl <file>                                           evaluates expressions interactively
+    tui <file>                                            opens the sheet in a terminal viewer/editor
+    watch <file>                                          evaluates the sheet again whenever it changes
+
+`echcell <file>` is the same as `echcell eval <file>`. A file of `-` is read from the standard input,
+an output of `-` is written to the standard output.
+Outputs can be templates: {dir}, {name}, {stem} and {ext} are replaced by the parts of the input path
+(e.g. -o \"{dir}/results/{stem}.csv\").
+Output formats are csv, json, markdown, html, table (an aligned terminal table), xlsx and ods (spreadsheets
+with native formulas); without --format the extension of the output decides and csv is the default.
+--stream evaluates the file row by row; tables need every row to align their columns and can't be streamed.
+--keep-formulas writes every expression of a csv output as its value followed by its formula
+(e.g. 2002|= CALC A2 * B2), so `refresh` can update the results in place.
+Every file can also be an xlsx or ods file: its first sheet (or the one given with --sheet) is loaded
+and its formulas are translated; formulas that can't be translated are reported and keep their values.
+Outputs are replaced only once they are written completely. --no-clobber refuses to replace an existing
+output, --backup keeps the old one with a .bak suffix.";
+
+/// How the problems of the evaluated cells are printed.
+#[derive(Clone, Copy)]
+pub enum DiagnosticsFormat {
+    Human,
+    Json,
+}
+
+/// Format of an exported dependency graph.
+#[derive(Clone, Copy)]
+pub enum GraphFormat {
+    Dot,
+    Json,
+}
+
+/// A parsed command line.
+pub enum Command {
+    Eval { input: String, output: Option<String>, settings: EvalSettings },
+    Check { inputs: Vec<String>, diagnostics: DiagnosticsFormat },
+    Get { input: String, cell: String, raw: bool },
+    Explain { input: String, cell: String },
+    Precedents { input: String, cell: String, direct: bool },
+    Dependents { input: String, cell: String, direct: bool },
+    Graph { input: String, format: GraphFormat, output: Option<String> },
+    Set { input: String, cell: String, item: String, output: Option<String>, overwrite: Overwrite },
+    Convert { input: String, output: String, settings: EvalSettings },
+    Import { input: String, sheet: Option<String>, output: Option<String>, overwrite: Overwrite },
+    Refresh { input: String, output: Option<String>, overwrite: Overwrite },
+    Fill { file: String, source: String, start: String, end: String },
+    Workbook { path: String },
+    Repl { file: String },
+    Tui { file: String },
+    Watch { file: String },
+    Help,
+}
+
+/// Settings of the eval and convert commands.
+#[derive(Default)]
+pub struct EvalSettings {
+    pub threads: Option<usize>,
+    pub stream: bool,
+    pub strict: bool,
+    pub diagnostics: Option<DiagnosticsFormat>,
+    pub format: Option<OutputFormat>,
+    pub spreadsheet: Option<SpreadsheetFormat>,
+    pub sheet: Option<String>,
+    pub keep_formulas: bool,
+    pub overwrite: Overwrite,
+}
+
+/// Options and positional arguments of a command.
+#[derive(Default)]
+struct Options {
+    positional: Vec<String>,
+    output: Option<String>,
+    threads: Option<usize>,
+    stream: bool,
+    strict: bool,
+    keep_formulas: bool,
+    raw: bool,
+    direct: bool,
+    format: Option<String>,
+    sheet: Option<String>,
+    diagnostics: Option<DiagnosticsFormat>,
+    overwrite: Overwrite,
+}
+
+/// Parses the command line arguments (without the program name).
+/// Returns a Result type of Command or a CsvError::RunError() with the usage of the command.
+pub fn parse(args: &[String]) -> Result<Command, CsvError> {
+    let Some(first) = args.first() else {
+        return Err(CsvError::RunError(format!("No CSV file was given...\n\n{}", USAGE)));
+    };
+
+    match first.as_str() {
+        "-h" | "--help" | "help" => Ok(Command::Help),
+        "eval" => parse_eval(&args[1..]),
+        "check" => {
+            let options = parse_options(&args[1..], &["--diagnostics"])?;
+            if options.positional.is_empty() {
+                return Err(CsvError::RunError("Usage: echcell check <file>... [--diagnostics=<human|json>]".to_string()));
+            }
+            Ok(Command::Check { inputs: options.positional, diagnostics: options.diagnostics.unwrap_or(DiagnosticsFormat::Human) })
+        },
+        "get" => {
+            let options = parse_options(&args[1..], &["--raw"])?;
+            let raw = options.raw;
+            let [input, cell] = get_positional(options, "get <file> <cell> [--raw]")?;
+            Ok(Command::Get { input, cell, raw })
+        },
+        "explain" => {
+            let [input, cell] = get_positional(parse_options(&args[1..], &[])?, "explain <file> <cell>")?;
+            Ok(Command::Explain { input, cell })
+        },
+        "precedents" | "dependents" => {
+            let options = parse_options(&args[1..], &["--direct"])?;
+            let direct = options.direct;
+            let [input, cell] = get_positional(options, &format!("{} <file> <cell> [--direct]", first))?;
+            if first == "precedents" {
+                Ok(Command::Precedents { input, cell, direct })
+            }
+            else {
+                Ok(Command::Dependents { input, cell, direct })
+            }
+        },
+        "graph" => {
+            let options = parse_options(&args[1..], &["--format", "-o", "--output"])?;
+            let format = match options.format.as_deref() {
+                None | Some("dot") => GraphFormat::Dot,
+                Some("json")       => GraphFormat::Json,
+                Some(_) => return Err(CsvError::RunError("--format expects dot or json...".to_string())),
+            };
+            let output = options.output.clone();
+            let [input] = get_positional(options, "graph <file> [--format=<dot|json>] [-o <output>]")?;
+            Ok(Command::Graph { input, format, output })
+        },
+        "set" => {
+            let options = parse_options(&args[1..], &["-o", "--output", "--no-clobber", "--backup"])?;
+            let (output, overwrite) = (options.output.clone(), options.overwrite);
+            let [input, cell, item] = get_positional(options, "set <file> <cell> <item> [-o <output>] [--no-clobber | --backup]")?;
+            Ok(Command::Set { input, cell, item, output, overwrite })
+        },
+        "convert" => {
+            let options = parse_options(
+                &args[1..],
+                &["-o", "--output", "--format", "--sheet", "--keep-formulas", "--strict", "--no-clobber", "--backup"],
+            )?;
+            let usage = "convert <file> -o <output> [--format <format>] [--sheet <name>] [--keep-formulas] [--strict] [--no-clobber | --backup]";
+            let Some(output) = options.output.clone() else {
+                return Err(CsvError::RunError(format!("Usage: echcell {}", usage)));
+            };
+            let settings = get_eval_settings(&options)?;
+            let [input] = get_positional(options, usage)?;
+            Ok(Command::Convert { input, output, settings })
+        },
+        "import" => {
+            let options = parse_options(&args[1..], &["--sheet", "-o", "--output", "--no-clobber", "--backup"])?;
+            let (sheet, output, overwrite) = (options.sheet.clone(), options.output.clone(), options.overwrite);
+            let [input] = get_positional(options, "import <file> [--sheet <name>] [-o <output>] [--no-clobber | --backup]")?;
+            Ok(Command::Import { input, sheet, output, overwrite })
+        },
+        "refresh" => {
+            let options = parse_options(&args[1..], &["-o", "--output", "--no-clobber", "--backup"])?;
+            let (output, overwrite) = (options.output.clone(), options.overwrite);
+            let [input] = get_positional(options, "refresh <file> [-o <output>] [--no-clobber | --backup]")?;
+            Ok(Command::Refresh { input, output, overwrite })
+        },
+        "fill" => {
+            let [file, source, start, end] = get_positional(parse_options(&args[1..], &[])?, "fill <file> <source> <start> <end>")?;
+            Ok(Command::Fill { file, source, start, end })
+        },
+        "workbook" => {
+            let [path] = get_positional(parse_options(&args[1..], &[])?, "workbook <directory or manifest>")?;
+            Ok(Command::Workbook { path })
+        },
+        "repl" => {
+            let [file] = get_positional(parse_options(&args[1..], &[])?, "repl <file>")?;
+            Ok(Command::Repl { file })
+        },
+        "tui" => {
+            let [file] = get_positional(parse_options(&args[1..], &[])?, "tui <file>")?;
+            Ok(Command::Tui { file })
+        },
+        "watch" => {
+            let [file] = get_positional(parse_options(&args[1..], &[])?, "watch <file>")?;
+            Ok(Command::Watch { file })
+        },
+        // (`echcell data.csv` evaluates the file)
+        _ => parse_eval(args),
+    }
+}
+
+/// Parses the arguments of the eval command.
+fn parse_eval(args: &[String]) -> Result<Command, CsvError> {
+    let options = parse_options(
+        args,
+        &["-o", "--output", "--format", "--sheet", "--keep-formulas", "--threads", "--stream", "--strict", "--diagnostics", "--no-clobber", "--backup"],
+    )?;
+    let (output, settings) = (options.output.clone(), get_eval_settings(&options)?);
+    let [input] = get_positional(
+        options,
+        "eval <file> [-o <output>] [--format <format>] [--sheet <name>] [--keep-formulas] [--threads <n>] [--stream] [--strict] \
+        [--diagnostics=<human|json>] [--no-clobber | --backup]",
+    )?;
+    Ok(Command::Eval { input, output, settings })
+}
+
+/// Returns the settings of the eval and convert commands from their options.
+fn get_eval_settings(options: &Options) -> Result<EvalSettings, CsvError> {
+    let (format, spreadsheet) = match options.format.as_deref() {
+        Some(name) => match (OutputFormat::from_name(name), SpreadsheetFormat::from_name(name)) {
+            (None, None) => return Err(CsvError::RunError("--format expects csv, json, markdown, html, table, xlsx or ods...".to_string())),
+            formats => formats,
+        },
+        None => (None, None),
+    };
+
+    Ok(EvalSettings {
+        threads: options.threads,
+        stream: options.stream,
+        strict: options.strict,
+        diagnostics: options.diagnostics,
+        format,
+        spreadsheet,
+        sheet: options.sheet.clone(),
+        keep_formulas: options.keep_formulas,
+        overwrite: options.overwrite,
+    })
+}
+
+/// Separates the options from the positional arguments.
+/// Only the given options are accepted; a lone `-` (standard input or output) and negative numbers are positional arguments.
+/// Values of options can be given as the next argument or after a '=' (`--threads 4`, `--threads=4`).
+fn parse_options(args: &[String], allowed: &[&str]) -> Result<Options, CsvError> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || arg == "-" || arg.parse::<f64>().is_ok() {
+            options.positional.push(arg.clone());
+            continue;
+        }
+
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None                => (arg.as_str(), None),
+        };
+        if !allowed.contains(&name) {
+            return Err(CsvError::RunError(format!("Unknown option {}...", name)));
+        }
+        let mut get_value = || value.or_else(|| args.next().map(String::as_str));
+
+        match name {
+            "-o" | "--output" => match get_value() {
+                Some(output) => options.output = Some(output.to_string()),
+                None         => return Err(CsvError::RunError(format!("{} expects an output path...", name))),
+            },
+            "--threads" => match get_value().and_then(|n| n.parse::<usize>().ok()) {
+                Some(threads) if threads > 0 => options.threads = Some(threads),
+                _ => return Err(CsvError::RunError("--threads expects a positive number...".to_string())),
+            },
+            "--diagnostics" => match get_value() {
+                Some("human") => options.diagnostics = Some(DiagnosticsFormat::Human),
+                Some("json")  => options.diagnostics = Some(DiagnosticsFormat::Json),
+                _ => return Err(CsvError::RunError("--diagnostics expects human or json...".to_string())),
+            },
+            "--stream" => options.stream = true,
+            "--strict" => options.strict = true,
+            "--keep-formulas" => options.keep_formulas = true,
+            "--raw"    => options.raw = true,
+            "--direct" => options.direct = true,
+            "--format" => match get_value() {
+                Some(format) => options.format = Some(format.to_string()),
+                None         => return Err(CsvError::RunError("--format expects a format name...".to_string())),
+            },
+            "--sheet" => match get_value() {
+                Some(sheet) => options.sheet = Some(sheet.to_string()),
+                None        => return Err(CsvError::RunError("--sheet expects a sheet name...".to_string())),
+            },
+            "--no-clobber" | "--backup" => {
+                let overwrite = if name == "--backup" { Overwrite::Backup } else { Overwrite::NoClobber };
+                if options.overwrite != Overwrite::Replace && options.overwrite != overwrite {
+                    return Err(CsvError::RunError("--no-clobber and --backup can't be used together...".to_string()));
+                }
+                options.overwrite = overwrite;
+            },
+            _ => {},
+        }
+    }
+
+    Ok(options)
+}
+
+/// Returns the positional arguments if there are exactly `N` of them (a CsvError::RunError() with the usage otherwise).
+fn get_positional<const N: usize>(options: Options, usage: &str) -> Result<[String; N], CsvError> {
+    match options.positional.try_into() {
+        Ok(positional) => Ok(positional),
+        Err(_)         => Err(CsvError::RunError(format!("Usage: echcell {}", usage))),
+    }
+}
+
+/// Runs a parsed command.
+/// Returns a Result type of () or the CsvError the command failed with.
+pub fn run(command: Command) -> Result<(), CsvError> {
+    match command {
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        },
+        Command::Eval { input, output, settings } => eval(&input, output.as_deref(), &settings),
+        Command::Check { inputs, diagnostics } => check_files(&inputs, diagnostics),
+        Command::Get { input, cell, raw } => {
+            let csv = load(&input)?;
+            if raw {
+                println!("{}", csv.get(&cell)?);
+            }
+            else {
+                // (Failing expressions are reported with the word that caused them)
+                println!("{}", csv.eval_cell(&cell).map_err(|err| csv.locate_error(&cell, err))?);
+            }
+            Ok(())
+        },
+        Command::Explain { input, cell } => {
+            println!("{}", load(&input)?.explain(&cell)?);
+            Ok(())
+        },
+        Command::Precedents { input, cell, direct } => {
+            load(&input)?.get_precedents(&cell, !direct)?.iter().for_each(|cell| println!("{}", cell));
+            Ok(())
+        },
+        Command::Dependents { input, cell, direct } => {
+            load(&input)?.get_dependents(&cell, !direct)?.iter().for_each(|cell| println!("{}", cell));
+            Ok(())
+        },
+        Command::Graph { input, format, output } => {
+            let csv = load(&input)?;
+            let graph = match format {
+                GraphFormat::Dot  => csv.to_dot(),
+                GraphFormat::Json => csv.to_graph_json(),
+            };
+
+            let output = output.map_or("-".to_string(), |output| get_output(&input, &output));
+            write_to(&output, Overwrite::Replace, |writer| match writeln!(writer, "{}", graph) {
+                Ok(_)  => Ok(()),
+                Err(_) => Err(CsvError::FileError("Could not write to output file...".to_string())),
+            })?;
+            print_done(&output);
+            Ok(())
+        },
+        Command::Set { input, cell, item, output, overwrite } => {
+            let mut csv = load(&input)?;
+            csv.set(&cell, &item)?;
+
+            // The sheet is saved in place unless an output is given (or it came from the standard input):
+            // (Sheets of spreadsheet files are saved as csv files next to them)
+            match output {
+                Some(output) => write_to(&get_output(&input, &output), overwrite, |writer| csv.write_source(writer))?,
+                None if input == "-" => write_to("-", overwrite, |writer| csv.write_source(writer))?,
+                None => write_atomic(Path::new(&csv.file), overwrite, |file| csv.write_source(file))?,
+            }
+            Ok(())
+        },
+        Command::Convert { input, output, settings } => eval(&input, Some(&output), &settings),
+        Command::Refresh { input, output, overwrite } => {
+            let mut csv = CSV::from_dual_file(&input)?;
+            csv.recalculate();
+
+            // The output is refreshed in place unless another output is given:
+            let output = output.map_or(input.clone(), |output| get_output(&input, &output));
+            write_to(&output, overwrite, |writer| write_output_dual(&csv, writer))?;
+            print_done(&output);
+            Ok(())
+        },
+        Command::Import { input, sheet, output, overwrite } => {
+            let csv = load_sheet(&input, sheet.as_deref())?;
+
+            // (The echcell sheet is written next to the spreadsheet file by default: "data.xlsx" -> "data.csv")
+            let output = output.map_or(csv.file.clone(), |output| get_output(&input, &output));
+            write_to(&output, overwrite, |writer| csv.write_source(writer))?;
+            print_done(&output);
+            Ok(())
+        },
+        Command::Fill { file, source, start, end } => {
+            let mut csv = load(&file)?;
+            csv.fill(&source, &start, &end)?;
+
+            // (Sheets from the standard input are written to the standard output)
+            match file.as_str() {
+                "-" => write_to("-", Overwrite::Replace, |writer| csv.write_source(writer))?,
+                _   => csv.save()?,
+            }
+            eprintln!("\n\tCells successfully filled!\n");
+            Ok(())
+        },
+        Command::Workbook { path } => {
+            let workbook = if Path::new(&path).is_dir() {
+                Workbook::open_dir(&path)?
+            }
+            else {
+                Workbook::open_manifest(&path)?
+            };
+
+            generate_outputs(&workbook)?;
+            eprintln!("\n\tOutput files successfully created!\n");
+            Ok(())
+        },
+        Command::Repl { file } => repl::run(&file),
+        Command::Tui { file } => tui::run(&file),
+        Command::Watch { file } => {
+            watch::run(&file);
+            Ok(())
+        },
+    }
+}
+
+/// Evaluates a sheet and writes its output (next to the input by default).
+/// The format is the given one, the one of the output extension or csv (a terminal table goes to the standard output by default).
+/// If a diagnostics format is given, the problems of the failing cells are printed too.
+/// In strict mode nothing is written if any cell fails (the error lists every failing cell).
+fn eval(input: &str, output: Option<&str>, settings: &EvalSettings) -> Result<(), CsvError> {
+    let output = output.map(|output| get_output(input, output));
+    let imported = SpreadsheetFormat::from_path(input).is_some();
+    let spreadsheet = match (settings.spreadsheet, settings.format) {
+        (None, None) => output.as_deref().and_then(SpreadsheetFormat::from_path),
+        (spreadsheet, _) => spreadsheet,
+    };
+    let format = settings.format
+        .or_else(|| output.as_deref().and_then(OutputFormat::from_path))
+        .unwrap_or(OutputFormat::Csv);
+    let output = match (output, spreadsheet) {
+        (Some(output), _) => output,
+        (None, Some(spreadsheet)) => get_output_path(input).with_extension(spreadsheet.get_extension()).display().to_string(),
+        (None, None) => match format {
+            _ if input == "-" => "-".to_string(),
+            OutputFormat::Table => "-".to_string(),
+            OutputFormat::Csv if !imported => get_output_path(input).display().to_string(),
+            _ => get_output_path(input).with_extension(format.get_extension()).display().to_string(),
+        },
+    };
+    let (strict, diagnostics) = (settings.strict, settings.diagnostics);
+    // (Spreadsheets keep their formulas anyway)
+    if settings.keep_formulas && spreadsheet.is_none() && format != OutputFormat::Csv {
+        return Err(CsvError::RunError("--keep-formulas only applies to csv outputs...".to_string()));
+    }
+
+    // Streaming mode:
+    // (Files whose formulas only reach their own row or a few rows back are evaluated without loading them fully;
+    // diagnostics, strict mode, kept formulas and spreadsheets need the whole sheet)
+    if settings.stream && format == OutputFormat::Table {
+        return Err(CsvError::RunError("--stream can't write tables: the columns are aligned to every row of the sheet...".to_string()));
+    }
+    if settings.stream && input != "-" && !imported && !strict && !settings.keep_formulas && diagnostics.is_none() && spreadsheet.is_none() {
+        match get_stream_window(input)? {
+            Some(window) => {
+                write_to(&output, settings.overwrite, |writer| write_output_streaming_as(input, window, writer, format.get_writer().as_mut()))?;
+                print_done(&output);
+                return Ok(());
+            },
+            None => eprintln!("\n\tA formula reaches a later row or more than {} rows back; loading the whole file...", MAX_STREAM_WINDOW),
+        }
+    }
+
+    // Every cell is calculated once in dependency order first:
+    let mut csv = load_sheet(input, settings.sheet.as_deref())?;
+    match settings.threads {
+        Some(threads) => csv.recalculate_with_threads(threads),
+        None          => csv.recalculate(),
+    }
+
+    // Every cell is checked before the output is touched:
+    // (The failing cells are listed once: by the diagnostics if they're printed, by the error otherwise)
+    if strict {
+        if let Some(format) = diagnostics {
+            print_diagnostics(&check(&csv), 1, format, output == "-");
+            check_strict(&csv).map_err(|err| CsvError::ReportedError(Box::new(err)))?;
+        }
+        check_strict(&csv)?;
+    }
+
+    match spreadsheet {
+        Some(spreadsheet) => write_to(&output, settings.overwrite, |writer| write_spreadsheet(&csv, spreadsheet, writer))?,
+        None if settings.keep_formulas => write_to(&output, settings.overwrite, |writer| write_output_dual(&csv, writer))?,
+        None => write_to(&output, settings.overwrite, |writer| write_output_as(&csv, writer, format.get_writer().as_mut()))?,
+    }
+    print_done(&output);
+
+    // (Diagnostics go to the standard error if the standard output holds the sheet)
+    if let (Some(format), false) = (diagnostics, strict) {
+        let diagnostics = check(&csv);
+        print_diagnostics(&diagnostics, 1, format, output == "-");
+        if matches!(format, DiagnosticsFormat::Human) && !diagnostics.is_empty() {
+            eprintln!("\n\t{} problem(s) found!\n", diagnostics.len());
+        }
+    }
+    Ok(())
+}
+
+/// Checks every formula of the given sheets and prints every problem (see `print_diagnostics`).
+/// Returns a CsvError::CheckError() with the amount of problems if any was found.
+fn check_files(inputs: &[String], format: DiagnosticsFormat) -> Result<(), CsvError> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for input in inputs {
+        let mut csv = load(input)?;
+        csv.recalculate();
+        diagnostics.extend(check(&csv));
+    }
+
+    print_diagnostics(&diagnostics, inputs.len(), format, false);
+    match diagnostics.is_empty() {
+        true  => Ok(()),
+        false => Err(CsvError::CheckError(diagnostics.len())),
+    }
+}
+
+/// Prints diagnostics like compiler errors (see `Diagnostic::render`) or as 1 JSON object per line followed by a summary object.
+fn print_diagnostics(diagnostics: &[Diagnostic], files: usize, format: DiagnosticsFormat, to_stderr: bool) {
+    let lines: Vec<String> = match format {
+        DiagnosticsFormat::Human => diagnostics.iter().map(|diagnostic| format!("{}\n", diagnostic)).collect(),
+        DiagnosticsFormat::Json  => diagnostics.iter().map(Diagnostic::to_json)
+            .chain([get_json_summary(files, diagnostics.len())])
+            .collect(),
+    };
+
+    lines.iter().for_each(|line| {
+        if to_stderr {
+            eprintln!("{}", line);
+        }
+        else {
+            println!("{}", line);
+        }
+    });
+
+    // (The human summary goes to the standard error so it doesn't mix with the diagnostics;
+    // the amount of problems is left to the caller, failing commands report it with their error)
+    if let (DiagnosticsFormat::Human, true) = (format, diagnostics.is_empty()) {
+        eprintln!("\n\tNo problems found!\n");
+    }
+}
+
+/// Loads a sheet from a file or from the standard input (`-`).
+fn load(input: &str) -> Result<CSV, CsvError> {
+    load_sheet(input, None)
+}
+
+/// Loads a sheet like `load`; the sheet of an xlsx or ods file is the one with the given name (the first one by default).
+/// Formulas of the spreadsheet that can't be translated are reported with their cells on the standard error.
+fn load_sheet(input: &str, sheet: Option<&str>) -> Result<CSV, CsvError> {
+    if SpreadsheetFormat::from_path(input).is_some() {
+        let (csv, unsupported) = CSV::from_spreadsheet(input, sheet)?;
+        unsupported.iter().for_each(|(cell, formula)| eprintln!("\t{}: unsupported formula {} (its value was loaded)", cell, formula));
+        return Ok(csv);
+    }
+    if sheet.is_some() {
+        return Err(CsvError::RunError("--sheet only applies to xlsx and ods files...".to_string()));
+    }
+
+    if input == "-" {
+        CSV::from_reader(STDIN_NAME, io::stdin().lock())
+    }
+    else {
+        CSV::new(input.to_string())
+    }
+}
+
+/// Returns the output path of an output template.
+/// {dir}, {name}, {stem} and {ext} are replaced by the directory, file name, file name without extension and extension of the input.
+fn get_output(input: &str, template: &str) -> String {
+    if template == "-" {
+        return template.to_string();
+    }
+
+    let path = Path::new(if input == "-" { STDIN_NAME } else { input });
+    let dir = path.parent().map(|dir| dir.to_string_lossy()).filter(|dir| !dir.is_empty()).unwrap_or(".".into());
+    let part = |part: Option<&std::ffi::OsStr>| part.map(|part| part.to_string_lossy().to_string()).unwrap_or_default();
+
+    template
+        .replace("{dir}", &dir)
+        .replace("{name}", &part(path.file_name()))
+        .replace("{stem}", &part(path.file_stem()))
+        .replace("{ext}", &part(path.extension()))
+}
+
+/// Writes an output with the given function: to the standard output for `-`, or atomically to a file
+/// (see `write_atomic`; missing parent directories are created first).
+fn write_to(output: &str, overwrite: Overwrite, write: impl FnOnce(&mut Box<dyn Write + '_>) -> Result<(), CsvError>) -> Result<(), CsvError> {
+    if output != "-" {
+        // (Directories of templated outputs are created)
+        if let Some(dir) = Path::new(output).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            let _ = fs::create_dir_all(dir);
+        }
+
+        return write_atomic(Path::new(output), overwrite, |file| {
+            let mut writer: Box<dyn Write + '_> = Box::new(file);
+            write(&mut writer)
+        });
+    }
+
+    let mut writer: Box<dyn Write> = Box::new(BufWriter::new(io::stdout().lock()));
+    write(&mut writer)?;
+    match writer.flush() {
+        Ok(_)  => Ok(()),
+        Err(_) => Err(CsvError::FileError("Could not write to output file...".to_string())),
+    }
+}
+
+/// Tells where the output was written (nothing is printed if it went to the standard output).
+/// The message goes to the standard error, so the standard output only holds the sheet or the diagnostics.
+fn print_done(output: &str) {
+    if output != "-" {
+        eprintln!("\n\tOutput file successfully created! ({})\n", output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a command line given as 1 string.
+    fn parse_line(line: &str) -> Result<Command, CsvError> {
+        parse(&line.split(' ').map(String::from).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn commands_are_parsed_with_their_options() {
+        let Ok(Command::Eval { input, output, settings }) = parse_line("data.csv -o=out.json --threads 4 --backup") else {
+            panic!("a file is evaluated by default");
+        };
+        assert_eq!((input.as_str(), output.as_deref()), ("data.csv", Some("out.json")));
+        assert_eq!((settings.threads, settings.overwrite), (Some(4), Overwrite::Backup));
+
+        let Ok(Command::Set { cell, item, .. }) = parse_line("set data.csv A1 -3") else { panic!("set has 3 arguments") };
+        assert_eq!((cell.as_str(), item.as_str()), ("A1", "-3"));
+        let Ok(Command::Convert { settings, .. }) = parse_line("convert data.csv -o out --format ods") else { panic!("convert has an output") };
+        assert_eq!(settings.spreadsheet, Some(SpreadsheetFormat::Ods));
+        assert!(matches!(parse_line("precedents data.csv C1 --direct"), Ok(Command::Precedents { direct: true, .. })));
+
+        for line in ["eval data.csv --raw", "convert data.csv", "get data.csv", "eval data.csv --threads 0", "set a.csv A1 1 --backup --no-clobber"] {
+            assert!(matches!(parse_line(line), Err(CsvError::RunError(_))), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn outputs_are_templated() {
+        assert_eq!(get_output("data/sales.csv", "{dir}/results/{stem}.{ext}"), "data/results/sales.csv");
+        assert_eq!(get_output("sales.csv", "{dir}/out_{name}"), "./out_sales.csv");
+        assert_eq!(get_output("sales.csv", "-"), "-");
+    }
+
+    #[test]
+    fn outputs_are_written_to_their_templates() {
+        let dir = std::env::temp_dir().join(format!("echcell_{}_cli", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("sales.csv").display().to_string();
+        fs::write(&input, "A,B\n2,= CALC A1 * 3\n").unwrap();
+
+        run(parse_line(&format!("{} -o {{dir}}/results/{{stem}}.md", input)).unwrap()).unwrap();
+        assert!(fs::read_to_string(dir.join("results/sales.md")).unwrap().contains("| 2 | 6 |"));
+
+        // (Existing outputs are kept with --no-clobber)
+        let result = run(parse_line(&format!("{} -o {{dir}}/results/{{stem}}.md --no-clobber", input)).unwrap());
+        assert!(matches!(result, Err(CsvError::FileError(_))));
+
+        // (Tables can't be streamed)
+        let result = run(parse_line(&format!("{} -o {{dir}}/sales.txt --stream", input)).unwrap());
+        assert!(matches!(result, Err(CsvError::RunError(_))));
+        assert!(!dir.join("sales.txt").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn output_formats_are_picked_by_option_or_extension() {
+        let dir = std::env::temp_dir().join(format!("echcell_{}_formats", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("sales.csv").display().to_string();
+        fs::write(&input, "A,B\n2,= CALC A1 * 3\n").unwrap();
+
+        // (Without an output the file is named after the format)
+        run(parse_line(&format!("{} --format json", input)).unwrap()).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("out_sales.json")).unwrap(), "[\n  {\"A\":2,\"B\":6}\n]\n");
+
+        // The extension decides unless a format is given:
+        run(parse_line(&format!("convert {} -o {{dir}}/report.html", input)).unwrap()).unwrap();
+        assert!(fs::read_to_string(dir.join("report.html")).unwrap().starts_with("<table>"));
+        run(parse_line(&format!("convert {} -o {{dir}}/report.html --format md", input)).unwrap()).unwrap();
+        assert!(fs::read_to_string(dir.join("report.html")).unwrap().starts_with("| A | B |"));
+        run(parse_line(&format!("convert {} -o {{dir}}/report.data", input)).unwrap()).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("report.data")).unwrap(), "A,B\n2,6\n");
+
+        assert!(matches!(parse_line(&format!("{} --format yaml", input)), Err(CsvError::RunError(_))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn spreadsheets_are_imported_and_evaluated() {
+        let dir = std::env::temp_dir().join(format!("echcell_{}_import", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("prices.csv").display().to_string();
+        let source = "Price,Qty,a,b:= CALC a# * 2\nApple,3,2,\nPear,5,4,= SUM a1 a2\n";
+        fs::write(&input, source).unwrap();
+
+        for ext in ["xlsx", "ods"] {
+            let book = dir.join(format!("book.{}", ext)).display().to_string();
+            run(parse_line(&format!("convert {} -o {}", input, book)).unwrap()).unwrap();
+
+            // (The sheet is written next to the spreadsheet and evaluated outputs are csv files)
+            run(parse_line(&format!("import {}", book)).unwrap()).unwrap();
+            assert_eq!(fs::read_to_string(dir.join("book.csv")).unwrap(), source, "{}", ext);
+            run(parse_line(&book).unwrap()).unwrap();
+            assert_eq!(fs::read_to_string(dir.join("out_book.csv")).unwrap(), "Price,Qty,a,b\nApple,3,2,4\nPear,5,4,6\n", "{}", ext);
+
+            assert!(run(parse_line(&format!("import {} --sheet missing", book)).unwrap()).is_err(), "{}", ext);
+            assert!(matches!(run(parse_line(&format!("import {} --no-clobber", book)).unwrap()), Err(CsvError::FileError(_))), "{}", ext);
+            fs::remove_file(dir.join("book.csv")).unwrap();
+        }
+
+        assert!(matches!(run(parse_line(&format!("{} --sheet prices", input)).unwrap()), Err(CsvError::RunError(_))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn kept_formulas_are_refreshed() {
+        let dir = std::env::temp_dir().join(format!("echcell_{}_dual", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("sales.csv").display().to_string();
+        fs::write(&input, "A,B,C:= CALC A# + B#\n2,= CALC A1 * 3,\n").unwrap();
+
+        run(parse_line(&format!("{} --keep-formulas", input)).unwrap()).unwrap();
+        let output = dir.join("out_sales.csv");
+        assert_eq!(fs::read_to_string(&output).unwrap(), "A,B,C:= CALC A# + B#\n2,6|= CALC A1 * 3,8|\n");
+
+        // (Values typed into the output are evaluated again in place)
+        fs::write(&output, "A,B,C:= CALC A# + B#\n5,6|= CALC A1 * 3,8|\n").unwrap();
+        run(parse_line(&format!("refresh {}", output.display())).unwrap()).unwrap();
+        assert_eq!(fs::read_to_string(&output).unwrap(), "A,B,C:= CALC A# + B#\n5,15|= CALC A1 * 3,20|\n");
+
+        run(parse_line(&format!("refresh {} -o {{dir}}/copy.csv", output.display())).unwrap()).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("copy.csv")).unwrap(), fs::read_to_string(&output).unwrap());
+
+        let result = run(parse_line(&format!("{} --keep-formulas --format json", input)).unwrap());
+        assert!(matches!(result, Err(CsvError::RunError(_))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn set_keeps_or_backs_up_existing_outputs() {
+        let dir = std::env::temp_dir().join(format!("echcell_{}_set", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("data.csv").display().to_string();
+        fs::write(&input, "A,B\n1,= CALC A1 * 2\n").unwrap();
+        fs::write(dir.join("edited.csv"), "old\n").unwrap();
+
+        let result = run(parse_line(&format!("set {} A1 2 -o {{dir}}/edited.csv --no-clobber", input)).unwrap());
+        assert!(matches!(result, Err(CsvError::FileError(_))));
+        assert_eq!(fs::read_to_string(dir.join("edited.csv")).unwrap(), "old\n");
+
+        run(parse_line(&format!("set {} A1 2 -o {{dir}}/edited.csv --backup", input)).unwrap()).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("edited.csv")).unwrap(), "A,B\n2,= CALC A1 * 2\n");
+        assert_eq!(fs::read_to_string(dir.join("edited.csv.bak")).unwrap(), "old\n");
+        // (The input is only replaced without an output)
+        assert_eq!(fs::read_to_string(&input).unwrap(), "A,B\n1,= CALC A1 * 2\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failed_checks_are_returned_as_errors() {
+        let dir = std::env::temp_dir().join(format!("echcell_{}_check", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("bad.csv").display().to_string();
+        fs::write(&input, "A,B\n1,= CALC A1 + x\n2,= SUMM A2\n").unwrap();
+
+        let Err(err) = run(parse_line(&format!("check {}", input)).unwrap()) else { panic!("the sheet has 2 problems") };
+        assert!(matches!(err, CsvError::CheckError(2)));
+        assert_eq!(err.get_exit_code(), 1);
+
+        // (With diagnostics the strict error only keeps its summary, the cells were already printed)
+        let Err(err) = run(parse_line(&format!("eval {} --strict --diagnostics=json", input)).unwrap()) else { panic!("2 cells fail") };
+        assert_eq!(err.to_string(), "#[STRICT ERROR] 2 cell(s) failed to evaluate...");
+        assert_eq!(err.get_exit_code(), 10);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
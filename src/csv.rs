@@ -1,16 +1,67 @@
-mod exprs;
-mod funcs;
+mod atomic;
+mod deps;
+mod diagnostic;
+mod dual;
+mod explain;
+mod format;
+pub mod exprs;
+pub mod funcs;
+mod graph;
+mod links;
+mod lint;
+mod refs;
+mod spreadsheet;
+mod storage;
+mod stream;
+mod value;
+mod workbook;
 
 use std::fs;
-use std::io::Write;
+use std::collections::HashMap;
+use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::num::NonZeroUsize;
+use std::panic;
+use std::thread;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use crate::error::CsvError;
-use exprs::eval;
+use exprs::eval_expr;
+use graph::{Cell, DepGraph, Recalc, get_references};
+use links::{Links, LinkHandle};
+use refs::shift_formula;
+use storage::Storage;
+
+pub use atomic::{Overwrite, write_atomic};
+pub use diagnostic::{Diagnostic, get_json_summary};
+pub use dual::{DUAL_SEPARATOR, strip_dual_item, write_output_dual};
+pub use explain::Explanation;
+pub use format::{OutputWriter, OutputFormat, CsvWriter, JsonWriter, MarkdownWriter, HtmlWriter, TableWriter};
+pub use lint::check;
+pub use refs::CellRef;
+pub use spreadsheet::{SpreadsheetFormat, from_native_formula, to_native_formula, write_spreadsheet};
+pub use stream::{MAX_STREAM_WINDOW, get_stream_window, generate_output_streaming, write_output_streaming, write_output_streaming_as};
+pub use value::Value;
+pub use workbook::{Workbook, generate_outputs};
 
 #[derive(Debug)]
 pub struct CSV {
     pub file: String,
     pub header: Vec<String>,
-    pub body: Vec<Vec<String>>,
+    body: Storage,
+    pub column_formulas: Vec<Option<String>>,
+    links: LinkHandle,
+    key: PathBuf,
+    calc: Option<Recalc>,
+}
+
+/// Minimum amount of independent cells to evaluate them on multiple threads.
+const PARALLEL_MIN_CELLS: usize = 512;
+
+thread_local! {
+    /// Cells that are currently being evaluated (file key and coordinates).
+    /// Used to detect circular references, including the ones going through linked files.
+    static EVAL_STACK: RefCell<Vec<(PathBuf, usize, usize)>> = const { RefCell::new(Vec::new()) };
 }
 
 /// Defines the type of acceptable ranges.
@@ -39,8 +90,42 @@ impl CSV {
     /// Creates a new CSV object.
     /// Returns a Result type of Self(CSV) or std::io::Error.
     pub fn new(file_path: String) -> Result<Self, CsvError> {
+        Self::new_linked(file_path, LinkHandle::Owned(Arc::new(Links::default())))
+    }
+
+    /// Creates a new CSV object from the contents of a csv file.
+    /// The name is used like a file path: linked files are resolved relative to it and the output file is named after it.
+    pub fn from_string(name: &str, contents: &str) -> Result<Self, CsvError> {
+        let (header, body) = Self::parse_contents(contents)?;
+        Ok(Self::from_rows(name, header, body))
+    }
+
+    /// Creates a new CSV object by reading csv contents from a reader.
+    pub fn from_reader(name: &str, mut reader: impl Read) -> Result<Self, CsvError> {
+        let mut contents = String::new();
+        match reader.read_to_string(&mut contents) {
+            Ok(_)  => Self::from_string(name, &contents),
+            Err(_) => Err(CsvError::FileError("Could not read csv contents...".to_string())),
+        }
+    }
+
+    /// Creates a new CSV object from a header and body rows.
+    /// Header items can declare column formulas the same way as in a csv file (`C:= CALC A# * B#`).
+    pub fn from_rows(name: &str, header: Vec<String>, body: Vec<Vec<String>>) -> Self {
+        Self::from_parts(name.to_string(), header, body, LinkHandle::Owned(Arc::new(Links::default())))
+    }
+
+    /// Creates a new CSV object that uses the given links to reach other csv files.
+    fn new_linked(file_path: String, links: LinkHandle) -> Result<Self, CsvError> {
         let parsed_data = Self::parse(&file_path)?;
-        Ok(Self { file: file_path, header: parsed_data.0, body: parsed_data.1 })
+        Ok(Self::from_parts(file_path, parsed_data.0, parsed_data.1, links))
+    }
+
+    /// Assembles a CSV object from its parsed parts.
+    fn from_parts(file: String, raw_header: Vec<String>, body: Vec<Vec<String>>, links: LinkHandle) -> Self {
+        let (header, column_formulas) = Self::parse_header(raw_header);
+        let key = fs::canonicalize(&file).unwrap_or_else(|_| PathBuf::from(&file));
+        Self { file, header, body: Storage::from_rows(body), column_formulas, links, key, calc: None }
     }
 
     /// -- PRIVATE --
@@ -48,19 +133,25 @@ impl CSV {
     /// Parses input into a tuple of Vec<String>(CSV header) and Vec<Vec<String>>(CSV body).
     /// Returns a Result type of the tuple or CsvError. 
     fn parse(file_path: &String) -> Result<(Vec<String>, Vec<Vec<String>>), CsvError> {
-        // Splitting file into lines:
-        let lines: Vec<String> = match fs::read_to_string(file_path) {
-            Ok(contents) => contents.lines().map(String::from).collect(),
-            Err(_) => return Err(CsvError::FileError("Could not read csv file...".to_string())),
-        };
+        match fs::read_to_string(file_path) {
+            Ok(contents) => Self::parse_contents(&contents),
+            Err(_) => Err(CsvError::FileError("Could not read csv file...".to_string())),
+        }
+    }
 
+    /// Parses the contents of a csv file (see `parse`).
+    fn parse_contents(contents: &str) -> Result<(Vec<String>, Vec<Vec<String>>), CsvError> {
         // Splitting lines by commas:
         let mut data: Vec<Vec<String>> = Vec::new();
-        for line in lines.iter() {
+        for line in contents.lines() {
             let split_line: Vec<String> = line.split(',').map(String::from).collect();
             data.push(split_line);
         }
 
+        if data.is_empty() {
+            return Err(CsvError::FileError("Csv file has no header...".to_string()));
+        }
+
         // Spearating to header and body:
         let header: Vec<String> = data[0].to_vec();
         let body: Vec<Vec<String>> = data[1..data.len()].to_vec();
@@ -68,9 +159,28 @@ impl CSV {
         Ok((header, body))
     }
 
+    /// Separates the column names from the column formulas declared in the header.
+    /// A header item like `C:= CALC A# * B#` declares column `C` with a formula that is applied to every body row.
+    /// Returns a tuple of the column names and the column formulas (None if a column doesn't have one).
+    fn parse_header(raw_header: Vec<String>) -> (Vec<String>, Vec<Option<String>>) {
+        raw_header.into_iter().map(|item| {
+            match item.split_once(':') {
+                Some((name, formula)) if formula.starts_with('=') => (name.trim().to_string(), Some(formula.to_string())),
+                _ => (item, None),
+            }
+        }).unzip()
+    }
+
     /// Receives a cell pointer and returns a column index or a CsvError::CellPError().
+    /// (`$` markers of absolute references are ignored.)
     fn get_column_cor(&self, cell_pointer: &str) -> Result<usize, CsvError> {
-        match self.header.iter().position(|col| col == &cell_pointer[0..1]) {
+        // The column is the first character (which isn't always 1 byte long):
+        let column = match cell_pointer.replace('$', "").chars().next() {
+            Some(column) => column.to_string(),
+            None         => return Err(CsvError::CellPError("Empty cell pointer...".to_string())),
+        };
+
+        match self.header.iter().position(|col| *col == column) {
             Some(val) => Ok(val),
             None             => Err(CsvError::CellPError("Column index out of bounds...".to_string())), 
         }
@@ -80,35 +190,144 @@ impl CSV {
     /// Also checks whether the row coordinate is in bounds.
     fn get_row_cor(&self, cell_pointer: &str) -> Result<usize, CsvError> {
         // Getting the coordinate:
-        let cor = match cell_pointer[1..cell_pointer.len()].parse::<usize>() {
-            Ok(val) => val - 1,
-            Err(_)         => return Err(CsvError::CellPError("Incorrect row index specifier...".to_string())),
-        };
+        let cor = Self::parse_row_cor(cell_pointer)?;
 
         // Checking whether it's outside of bounds:
-        if cor > self.body.len() {
+        if cor >= self.body.len() {
             return Err(CsvError::CellPError("Row index out of bounds...".to_string()));
         }
 
         Ok(cor)
     }
 
+    /// Receives a cell pointer and returns its row index without checking the bounds.
+    fn parse_row_cor(cell_pointer: &str) -> Result<usize, CsvError> {
+        let cell_pointer = cell_pointer.replace('$', "");
+        let mut chars = cell_pointer.chars();
+        chars.next();
+
+        match chars.as_str().parse::<usize>().ok() {
+            Some(val) if val > 0 => Ok(val - 1),
+            _                    => Err(CsvError::CellPError("Incorrect row index specifier...".to_string())),
+        }
+    }
+
     /// Returns a Result type of item (String. An item from the csv body.) or a CsvError with a specified error message.
     /// This function can be called on a CSV object and takes in a cell pointer in this format: "A1", "C2", ...
-    fn get_cell_value(&self, cell_pointer: &str) -> Result<String, CsvError> {
+    /// Cell pointers of other csv files are prefixed with the file name: "rates.csv!B2".
+    /// If the cell contains an expression its evaluated value is returned.
+    pub fn get_cell_value(&self, cell_pointer: &str) -> Result<String, CsvError> {
+        // Cell of a linked file:
+        if let Some((file, cell_pointer)) = cell_pointer.split_once('!') {
+            return self.get_linked_sheet(file)?.get_cell_value(cell_pointer);
+        }
+
         // Getting the x coordinate:
         let x_cor = self.get_column_cor(cell_pointer)?;
         // Getting the y coordinate:
         let y_cor = self.get_row_cor(cell_pointer)?;
 
-        Ok(self.body[y_cor][x_cor].to_string())
+        self.get_evaluated_value(x_cor, y_cor)
+    }
+
+    /// Returns the value of a cell as a number or None if the value isn't a number.
+    /// Numbers stored in the body are read without parsing them again.
+    pub fn get_cell_number(&self, cell_pointer: &str) -> Result<Option<f64>, CsvError> {
+        // Cell of a linked file:
+        if let Some((file, cell_pointer)) = cell_pointer.split_once('!') {
+            return self.get_linked_sheet(file)?.get_cell_number(cell_pointer);
+        }
+
+        let x_cor = self.get_column_cor(cell_pointer)?;
+        let y_cor = self.get_row_cor(cell_pointer)?;
+
+        match self.body.get_number(x_cor, y_cor) {
+            Some(n) => Ok(Some(n)),
+            None    => Ok(self.get_evaluated_value(x_cor, y_cor)?.parse::<f64>().ok()),
+        }
+    }
+
+    /// Returns the value of the cell at the given coordinates.
+    /// Expressions are evaluated; a cell that (indirectly) references itself results in a CsvError::CycleError().
+    fn get_evaluated_value(&self, x_cor: usize, y_cor: usize) -> Result<String, CsvError> {
+        let item = self.get_cell_source(x_cor, y_cor);
+        if !item.starts_with('=') {
+            return Ok(item);
+        }
+
+        // Cached value (see `recalculate`):
+        if let Some(value) = self.calc.as_ref().and_then(|calc| calc.values.get(&(x_cor, y_cor))) {
+            return value.clone();
+        }
+
+        let key = (self.key.clone(), x_cor, y_cor);
+        if EVAL_STACK.with(|stack| stack.borrow().contains(&key)) {
+            return Err(CsvError::CycleError(format!("Circular reference through {}!{}{}...", self.file, self.header[x_cor], y_cor + 1)));
+        }
+
+        EVAL_STACK.with(|stack| stack.borrow_mut().push(key));
+        let value = eval_expr(&item, self);
+        EVAL_STACK.with(|stack| stack.borrow_mut().pop());
+
+        value
+    }
+
+    /// Returns the linked csv file with the given name (loading it on first use).
+    /// Sheet names of the workbook are checked first, other names are resolved relative to the directory of this csv file.
+    fn get_linked_sheet(&self, file: &str) -> Result<Arc<CSV>, CsvError> {
+        let links = self.links.get()?;
+        if let Some(sheet) = links.get_named(file) {
+            return Ok(sheet);
+        }
+
+        let path = Path::new(&self.file).parent().unwrap_or(Path::new("")).join(file);
+        Links::load(&links, &path)
+    }
+
+    /// Returns the paths of the linked csv files loaded so far (the files reached by evaluated formulas).
+    pub fn get_linked_files(&self) -> Vec<PathBuf> {
+        match self.links.get() {
+            Ok(links) => links.get_paths().into_iter().filter(|path| *path != self.key).collect(),
+            Err(_)    => Vec::new(),
+        }
+    }
+
+    /// Returns the raw content of the cell at the given coordinates.
+    /// Empty cells in a column with a column formula receive the formula expanded for their row
+    /// (every `#` in the formula is replaced by the row number).
+    fn get_cell_source(&self, x_cor: usize, y_cor: usize) -> String {
+        let item = self.body.get(x_cor, y_cor);
+
+        match self.column_formulas.get(x_cor) {
+            Some(Some(formula)) if item.is_empty() => formula.replace('#', &(y_cor + 1).to_string()),
+            _ => item,
+        }
+    }
+
+    /// Returns the amount of cells in a given body row.
+    /// Rows that are shorter than a column with a column formula are extended up to that column.
+    fn get_row_width(&self, y_cor: usize) -> usize {
+        match self.column_formulas.iter().rposition(Option::is_some) {
+            Some(x) => self.body.get_row_width(y_cor).max(x + 1),
+            None    => self.body.get_row_width(y_cor),
+        }
     }
 
     /// Returns a Result type of a vector of strings or a specified error message.
     /// Receives 2 cell pointers a start of a range and an end of a range.
     /// The resulting vector of strings are the values of cells inside the given range.
     /// Either the column or row index must match on both cell pointers (ranges are either column base or row based; nothing diagonal).
-    fn get_range_values(&self, cell_pointer_start: &str, cell_pointer_end: &str) -> Result<Vec<String>, CsvError> {
+    /// Ranges of linked files must use the same file on both ends: "rates.csv!A1" "rates.csv!A5".
+    pub fn get_range_values(&self, cell_pointer_start: &str, cell_pointer_end: &str) -> Result<Vec<String>, CsvError> {
+        // Range of a linked file:
+        match (cell_pointer_start.split_once('!'), cell_pointer_end.split_once('!')) {
+            (Some((file_start, start)), Some((file_end, end))) if file_start == file_end => {
+                return self.get_linked_sheet(file_start)?.get_range_values(start, end);
+            },
+            (None, None) => {},
+            _ => return Err(CsvError::RangeError("A range must stay inside one file...".to_string())),
+        }
+
         // Getting the coordinates:
         let x_start = self.get_column_cor(cell_pointer_start)?;
         let x_end = self.get_column_cor(cell_pointer_end)?;
@@ -118,8 +337,18 @@ impl CSV {
         // Determining the range type (row, column or nil):
         if let Some(r_type) = RangeType::get_range_type(x_start, x_end, y_start, y_end) {
             match r_type {
-                RangeType::Row => Ok(self.body[y_start][x_start..=x_end].to_vec()),
-                RangeType::Col => Ok((y_start..=y_end).map(|y| self.body[y][x_start].clone()).collect()),
+                RangeType::Row => (x_start..=x_end).map(|x| self.get_evaluated_value(x, y_start)).collect(),
+                RangeType::Col if self.column_formulas.get(x_start).is_some_and(Option::is_some) => {
+                    (y_start..=y_end).map(|y| self.get_evaluated_value(x_start, y)).collect()
+                },
+                // Only the non-empty cells of the column are visited (empty regions of sparse sheets are skipped):
+                RangeType::Col => {
+                    let mut values = vec![String::new(); y_end + 1 - y_start];
+                    for (y, _) in self.body.get_column_cells(x_start, y_start, y_end) {
+                        values[y - y_start] = self.get_evaluated_value(x_start, y)?;
+                    }
+                    Ok(values)
+                },
             }
         }
         else {
@@ -127,39 +356,644 @@ impl CSV {
         }
     }
     
+    /// Returns the numbers of a column range as a slice without copying or parsing them.
+    /// Only works for column ranges of this file whose column holds a number in every row (returns None otherwise).
+    fn get_range_numbers(&self, cell_pointer_start: &str, cell_pointer_end: &str) -> Option<&[f64]> {
+        if cell_pointer_start.contains('!') || cell_pointer_end.contains('!') {
+            return None;
+        }
+
+        let x_start = self.get_column_cor(cell_pointer_start).ok()?;
+        let x_end = self.get_column_cor(cell_pointer_end).ok()?;
+        if x_start != x_end {
+            return None;
+        }
+
+        let y_start = self.get_row_cor(cell_pointer_start).ok()?;
+        let y_end = self.get_row_cor(cell_pointer_end).ok()?;
+        self.body.get_numbers(x_start, y_start, y_end)
+    }
+
     /// Returns the length of a range.
     /// Makes use of the `get_range_values` function.
     fn get_range_len(&self, cell_pointer_start: &str, cell_pointer_end: &str) -> Result<usize, CsvError> {
-        Ok(Self::get_range_values(&self, cell_pointer_start, cell_pointer_end)?.len())
+        Ok(Self::get_range_values(self, cell_pointer_start, cell_pointer_end)?.len())
+    }
+
+    /// Returns the amount of body rows.
+    pub fn get_row_count(&self) -> usize {
+        self.body.len()
+    }
+
+    /// Returns whether the body is stored sparsely (only its non-empty cells are kept).
+    /// Large and mostly empty sheets are stored sparsely automatically.
+    pub fn is_sparse(&self) -> bool {
+        self.body.is_sparse()
+    }
+
+    /// Returns the body rows with the raw content of their cells (expressions are not evaluated).
+    pub fn get_body(&self) -> Vec<Vec<String>> {
+        (0..self.body.len()).map(|y| self.body.get_row(y)).collect()
+    }
+
+    /// Replaces every body row; the storage is picked again for the new rows.
+    /// Cached values are calculated again (see `recalculate`).
+    pub fn set_body(&mut self, body: Vec<Vec<String>>) {
+        self.body = Storage::from_rows(body);
+        if let Some(threads) = self.calc.as_ref().map(|calc| calc.threads) {
+            self.recalculate_with_threads(threads);
+        }
+    }
+
+    /// Returns the raw content of a cell (expressions are not evaluated).
+    pub fn get(&self, cell_pointer: &str) -> Result<String, CsvError> {
+        if let Some((file, cell_pointer)) = cell_pointer.split_once('!') {
+            return self.get_linked_sheet(file)?.get(cell_pointer);
+        }
+
+        let x_cor = self.get_column_cor(cell_pointer)?;
+        let y_cor = self.get_row_cor(cell_pointer)?;
+        Ok(self.get_cell_source(x_cor, y_cor))
+    }
+
+    /// Overwrites the raw content of a cell with a value or an expression.
+    /// Setting a cell below the last row adds the missing (empty) rows.
+    pub fn set(&mut self, cell_pointer: &str, item: &str) -> Result<(), CsvError> {
+        if cell_pointer.contains('!') {
+            return Err(CsvError::CellPError("Cells of linked files can't be set...".to_string()));
+        }
+
+        let x_cor = self.get_column_cor(cell_pointer)?;
+        let y_cor = Self::parse_row_cor(cell_pointer)?;
+        Self::check_item(item)?;
+
+        self.write_cells(vec![(x_cor, y_cor, item.to_string())]);
+        Ok(())
+    }
+
+    /// Checks whether an item can be stored in a cell: the csv format has no quoting,
+    /// so commas and line breaks would split the cell when the file is written.
+    fn check_item(item: &str) -> Result<(), CsvError> {
+        if item.contains([',', '\n', '\r']) {
+            return Err(CsvError::RunError("Items can't contain commas or line breaks...".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Overwrites a cell like `set` and recalculates only the cells that depend on it.
+    /// The first update calculates every cell of the CSV object (see `recalculate`).
+    /// Returns the cell pointers of every cell whose value changed (including the updated cell).
+    pub fn update(&mut self, cell_pointer: &str, item: &str) -> Result<Vec<String>, CsvError> {
+        if self.calc.is_none() {
+            self.recalculate();
+        }
+
+        if cell_pointer.contains('!') {
+            return Err(CsvError::CellPError("Cells of linked files can't be set...".to_string()));
+        }
+
+        let x_cor = self.get_column_cor(cell_pointer)?;
+        let y_cor = Self::parse_row_cor(cell_pointer)?;
+        Self::check_item(item)?;
+
+        let changed = self.write_cells(vec![(x_cor, y_cor, item.to_string())]);
+        Ok(changed.into_iter().map(|(x, y)| self.get_cell_pointer(x, y)).collect())
+    }
+
+    /// Evaluates every expression in dependency order and caches the values.
+    /// From then on edits made through `set`, `update` and `fill` only recalculate the cells that depend on the edited ones.
+    /// Independent cells are evaluated in parallel on every available core.
+    pub fn recalculate(&mut self) {
+        let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        self.recalculate_with_threads(threads);
+    }
+
+    /// Same as `recalculate` but evaluates independent cells on the given amount of threads.
+    /// The amount is also used by later recalculations of the edited cells.
+    pub fn recalculate_with_threads(&mut self, threads: usize) {
+        let threads = threads.max(1);
+        let cells = self.get_expression_cells();
+        let graph = DepGraph::build(self, &cells, threads);
+        let levels = graph.levels(&cells.into_iter().collect());
+
+        self.calc = Some(Recalc { graph, values: HashMap::new(), threads });
+        self.evaluate_levels(&levels);
+    }
+
+    /// Writes cells and, if the values are cached, recalculates the written cells and their dependents.
+    /// Cells below the last row add the missing (empty) rows; their column formulas and the cells reading the new rows are recalculated too.
+    /// Returns the coordinates of the cells whose value changed in row order (empty if nothing is cached).
+    fn write_cells(&mut self, cells: Vec<(usize, usize, String)>) -> Vec<Cell> {
+        // Adding the missing rows:
+        let rows = self.body.len();
+        let new_rows = cells.iter().map(|(_, y, _)| y + 1).max().unwrap_or(0);
+        if new_rows > rows {
+            self.body.resize(new_rows);
+        }
+
+        // (Every cell of a new row is written too, so the cells that read it are marked dirty)
+        let written: Vec<Cell> = cells.iter().map(|(x, y, _)| (*x, *y))
+            .chain((rows..new_rows).flat_map(|y| (0..self.header.len()).map(move |x| (x, y))))
+            .collect();
+
+        // Marking the written cells and their dependents dirty:
+        let dirty = match &self.calc {
+            Some(calc) => calc.graph.get_affected(&written),
+            None => {
+                cells.into_iter().for_each(|(x, y, item)| self.set_cell_source(x, y, item));
+                return Vec::new();
+            },
+        };
+        let old_values: HashMap<Cell, String> = dirty.iter()
+            .filter(|(_, y)| *y < rows)
+            .map(|&(x, y)| ((x, y), self.get_output_value(x, y)))
+            .collect();
+
+        // Writing and updating the graph:
+        cells.into_iter().for_each(|(x, y, item)| self.set_cell_source(x, y, item));
+        let references: Vec<(Cell, Vec<Cell>)> = written.iter()
+            .map(|&(x, y)| ((x, y), get_references(&self.get_cell_source(x, y), self)))
+            .collect();
+
+        let levels = match &mut self.calc {
+            Some(calc) => {
+                references.into_iter().for_each(|(cell, precedents)| calc.graph.set_precedents(cell, precedents));
+                calc.values.retain(|cell, _| !dirty.contains(cell));
+                calc.graph.levels(&dirty)
+            },
+            None => return Vec::new(),
+        };
+
+        // Recalculating the dirty cells:
+        self.evaluate_levels(&levels);
+
+        // (Cells of new rows changed if they hold anything)
+        let mut changed: Vec<Cell> = dirty.into_iter()
+            .filter(|&(x, y)| match old_values.get(&(x, y)) {
+                Some(old) => *old != self.get_output_value(x, y),
+                None      => !self.get_cell_source(x, y).is_empty(),
+            })
+            .collect();
+        changed.sort_unstable_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+        changed
+    }
+
+    /// Evaluates the expressions of the given dependency levels in order and caches their values.
+    fn evaluate_levels(&mut self, levels: &[Vec<Cell>]) {
+        let threads = self.calc.as_ref().map_or(1, |calc| calc.threads);
+
+        levels.iter().for_each(|level| {
+            let cells: Vec<Cell> = level.iter().copied().filter(|&(x, y)| self.get_cell_source(x, y).starts_with('=')).collect();
+            let values = self.evaluate_level(&cells, threads);
+
+            if let Some(calc) = &mut self.calc {
+                calc.values.extend(values);
+            }
+        });
+    }
+
+    /// Evaluates cells that don't depend on each other.
+    /// The cells are split between the given amount of threads if there are enough of them.
+    fn evaluate_level(&self, cells: &[Cell], threads: usize) -> Vec<(Cell, Result<String, CsvError>)> {
+        let evaluate = |cells: &[Cell]| -> Vec<(Cell, Result<String, CsvError>)> {
+            cells.iter().map(|&(x, y)| ((x, y), self.get_evaluated_value(x, y))).collect()
+        };
+
+        // Small levels aren't worth the threads:
+        if threads == 1 || cells.len() < PARALLEL_MIN_CELLS {
+            return evaluate(cells);
+        }
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = cells.chunks(cells.len().div_ceil(threads))
+                .map(|chunk| scope.spawn(move || evaluate(chunk)))
+                .collect();
+
+            // (The results are joined in order, so the cache doesn't depend on the thread timing)
+            handles.into_iter()
+                .flat_map(|handle| handle.join().unwrap_or_else(|err| panic::resume_unwind(err)))
+                .collect()
+        })
+    }
+
+    /// Returns the text of a cell as it's written to the output file.
+    /// Empty cells are "#[NULL]" and failing expressions are replaced by their error message.
+    fn get_output_value(&self, x_cor: usize, y_cor: usize) -> String {
+        if self.get_cell_source(x_cor, y_cor).is_empty() {
+            return "#[NULL]".to_string();
+        }
+
+        match self.get_evaluated_value(x_cor, y_cor) {
+            Ok(val)  => val,
+            Err(err) => err.to_string(),
+        }
+    }
+
+    /// Returns the cells of a body row as they're written to the output file (see `get_output_value`).
+    pub fn get_output_row(&self, y_cor: usize) -> Vec<String> {
+        (0..self.get_row_width(y_cor)).map(|x| self.get_output_value(x, y_cor)).collect()
+    }
+
+    /// Returns every cell whose expression fails to evaluate, with its error (ordered by rows).
+    pub fn get_errors(&self) -> Vec<(String, CsvError)> {
+        self.get_cells().into_iter()
+            .filter_map(|(x, y)| match self.get_evaluated_value(x, y) {
+                Ok(_)    => None,
+                Err(err) => Some((self.get_cell_pointer(x, y), err)),
+            })
+            .collect()
+    }
+
+    /// Returns the cell pointer of the given coordinates ("?" stands for cells outside of the header).
+    fn get_cell_pointer(&self, x_cor: usize, y_cor: usize) -> String {
+        format!("{}{}", self.header.get(x_cor).map_or("?", String::as_str), y_cor + 1)
+    }
+
+    /// Returns the coordinates of every cell of the body (including the cells of column formulas).
+    fn get_cells(&self) -> Vec<Cell> {
+        (0..self.body.len()).flat_map(|y| (0..self.get_row_width(y)).map(move |x| (x, y))).collect()
+    }
+
+    /// Returns the coordinates of every cell that holds an expression.
+    fn get_expression_cells(&self) -> Vec<Cell> {
+        // (Without column formulas, empty cells can't hold an expression and are skipped)
+        let cells = if self.column_formulas.iter().all(Option::is_none) {
+            self.body.get_filled_cells()
+        }
+        else {
+            self.get_cells()
+        };
+
+        cells.into_iter()
+            .filter(|&(x, y)| self.get_cell_source(x, y).starts_with('='))
+            .collect()
+    }
+
+    /// Evaluates a cell and returns its typed value.
+    /// Unlike the output file, a failing expression is returned as its CsvError.
+    pub fn eval_cell(&self, cell_pointer: &str) -> Result<Value, CsvError> {
+        Ok(Value::from_item(&self.get_cell_value(cell_pointer)?))
+    }
+
+    /// Evaluates an expression against the cells of this CSV object and returns its typed value.
+    /// The leading '=' is optional: "SUM A1 A5" and "= SUM A1 A5" are the same.
+    pub fn eval_str(&self, expr: &str) -> Result<Value, CsvError> {
+        let expr = expr.trim_start();
+        let item = if expr.starts_with('=') {
+            expr.to_string()
+        }
+        else {
+            format!("= {}", expr)
+        };
+
+        Ok(Value::from_item(&eval_expr(&item, self)?))
+    }
+
+    /// Overwrites the raw content of the cell at the given coordinates.
+    /// Rows that are too short are padded with empty cells.
+    fn set_cell_source(&mut self, x_cor: usize, y_cor: usize, item: String) {
+        self.body.set(x_cor, y_cor, &item);
+    }
+
+    /// Copies the content of the source cell into every cell of the target range (fill/copy).
+    /// Relative references of a copied formula are shifted by the distance between the source and the target cell,
+    /// absolute references (marked with `$`) stay in place.
+    /// The target range follows the same rules as every other range (either row or column based).
+    pub fn fill(&mut self, source: &str, target_start: &str, target_end: &str) -> Result<(), CsvError> {
+        // Getting the coordinates:
+        let x_src = self.get_column_cor(source)?;
+        let y_src = self.get_row_cor(source)?;
+        let x_start = self.get_column_cor(target_start)?;
+        let x_end = self.get_column_cor(target_end)?;
+        let y_start = self.get_row_cor(target_start)?;
+        let y_end = self.get_row_cor(target_end)?;
+
+        let targets: Vec<(usize, usize)> = match RangeType::get_range_type(x_start, x_end, y_start, y_end) {
+            Some(RangeType::Row) => (x_start..=x_end).map(|x| (x, y_start)).collect(),
+            Some(RangeType::Col) => (y_start..=y_end).map(|y| (x_start, y)).collect(),
+            None => return Err(CsvError::RangeError("Unknown range type...".to_string())),
+        };
+
+        // Shifting every copy first so a failing target leaves the table untouched:
+        let item = self.get_cell_source(x_src, y_src);
+        let copies = targets.iter().map(|&(x, y)| {
+            let d_col = x as isize - x_src as isize;
+            let d_row = y as isize - y_src as isize;
+            Ok((x, y, shift_formula(&item, &self.header, d_col, d_row)?))
+        }).collect::<Result<Vec<_>, CsvError>>()?;
+
+        self.write_cells(copies);
+        Ok(())
+    }
+
+    /// Returns the header items as they're written in the input format (with their column formulas).
+    fn get_source_header(&self) -> Vec<String> {
+        self.header.iter().zip(self.column_formulas.iter())
+            .map(|(name, formula)| match formula {
+                Some(formula) => format!("{}:{}", name, formula),
+                None          => name.clone(),
+            })
+            .collect()
+    }
+
+    /// Writes the CSV object back to its file in the input format (column formulas stay in the header).
+    /// The file is replaced atomically (see `write_atomic`).
+    pub fn save(&self) -> Result<(), CsvError> {
+        write_atomic(Path::new(&self.file), Overwrite::Replace, |file| self.write_source(file))
+    }
+
+    /// Writes the CSV object in the input format to a writer (see `save`).
+    pub fn write_source(&self, writer: &mut impl Write) -> Result<(), CsvError> {
+        let mut buffer = self.get_source_header().join(",");
+        (0..self.body.len()).for_each(|y| {
+            buffer.push('\n');
+            buffer.push_str(&self.body.get_row(y).join(","));
+        });
+        buffer.push('\n');
+
+        match writer.write_all(buffer.as_bytes()) {
+            Ok(_)  => Ok(()),
+            Err(_) => Err(CsvError::FileError("Could not write csv file...".to_string())),
+        }
     }
 }
 
 /// Iterates over the created CSV object and evaluates all the expressions found and creates an output csv file.
 ///
 /// DOES NOT HANDLE INVALID EXPRESSIONS. (They will be parsed into the output file with an error message inside the corresponding cell).
+/// See `generate_output_strict` for a version that refuses to write them.
+/// The output file is replaced atomically, so a failed write never leaves a half-written file behind (see `write_atomic`).
 /// 
 /// Returns a Result type of () or CsvError if the file generation failed.
 pub fn generate_output(csv: &CSV) -> Result<(), CsvError> {
-    // Creating output file:
-    let mut output_file = match fs::File::create(format!("out_{}", csv.file)) {
-        Ok(f) => f,
-        Err(_) => return Err(CsvError::FileError("Could not create output file...".to_string())),
-    };
-    
-    // Writing the header to the output file:
-    match writeln!(&mut output_file, "{}", csv.header.join(",")) {
-        Ok(_)  => {},
-        Err(_) => return Err(CsvError::FileError("Could not write to output file...".to_string())),
+    generate_output_with(csv, Overwrite::Replace)
+}
+
+/// Same as `generate_output` but writes the output file in the given format
+/// (with the extension of the format: "data/x.csv" -> "data/out_x.json").
+pub fn generate_output_as(csv: &CSV, format: OutputFormat) -> Result<(), CsvError> {
+    let path = get_output_path(&csv.file).with_extension(format.get_extension());
+    write_atomic(&path, Overwrite::Replace, |output_file| write_output_as(csv, output_file, format.get_writer().as_mut()))
+}
+
+/// Same as `generate_output` but writes the output file as a spreadsheet with native formulas (see `write_spreadsheet`)
+/// ("data/x.csv" -> "data/out_x.xlsx").
+pub fn generate_spreadsheet(csv: &CSV, format: SpreadsheetFormat) -> Result<(), CsvError> {
+    let path = get_output_path(&csv.file).with_extension(format.get_extension());
+    write_atomic(&path, Overwrite::Replace, |output_file| write_spreadsheet(csv, format, output_file))
+}
+
+/// Same as `generate_output` but an existing output file is kept or backed up as requested.
+pub fn generate_output_with(csv: &CSV, overwrite: Overwrite) -> Result<(), CsvError> {
+    write_atomic(Path::new(&get_output_path(&csv.file)), overwrite, |output_file| write_output(csv, output_file))
+}
+
+/// Same as `generate_output` but every expression has to evaluate successfully.
+/// The cells are evaluated before the output file is touched, so nothing is written if any of them fails.
+/// Returns a Result type of () or a CsvError::StrictError() listing every failing cell.
+pub fn generate_output_strict(csv: &CSV) -> Result<(), CsvError> {
+    check_strict(csv)?;
+    generate_output(csv)
+}
+
+/// Returns a CsvError::StrictError() listing every failing cell of the CSV object (Ok if there is none).
+/// The errors are the diagnostics of `check` (as CsvError::CellError()), so both report the same problems with the same messages.
+pub fn check_strict(csv: &CSV) -> Result<(), CsvError> {
+    let errors: Vec<(String, CsvError)> = check(csv).into_iter()
+        .map(|diagnostic| (diagnostic.cell.clone(), CsvError::CellError(Box::new(diagnostic))))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
     }
-    
+    else {
+        Err(CsvError::StrictError(errors))
+    }
+}
+
+/// Writes the evaluated CSV object to a writer in the format of the output file (see `generate_output`).
+pub fn write_output(csv: &CSV, writer: &mut impl Write) -> Result<(), CsvError> {
+    write_output_as(csv, writer, &mut CsvWriter)
+}
+
+/// Writes the evaluated CSV object to a writer with the given output writer (see `OutputFormat` for the built-in ones).
+pub fn write_output_as(csv: &CSV, writer: &mut impl Write, output_writer: &mut dyn OutputWriter) -> Result<(), CsvError> {
+    // Writing the header:
+    output_writer.write_header(writer, &csv.header)?;
+
     // Writing the body and evaluating the expressions:
-    csv.body.iter().try_for_each(|row| {
-        let buffer: String = row.iter()
-            .map(|item| eval(item, &csv))
-            .collect::<Vec<_>>().join(",");
-        match writeln!(&mut output_file, "{}", buffer) {
-            Ok(_)  => Ok(()),
-            Err(_) => return Err(CsvError::FileError("Could not write to output file...".to_string())),
+    // (Column formulas are expanded for every row here)
+    (0..csv.body.len()).try_for_each(|y| output_writer.write_row(writer, &csv.get_output_row(y)))?;
+    output_writer.finish(writer)
+}
+
+/// Returns the path of the output file of a given csv file.
+/// The output file is created next to the input file with an `out_` prefix ("data/x.csv" -> "data/out_x.csv").
+pub fn get_output_path(file: &str) -> PathBuf {
+    let path = Path::new(file);
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!("out_{}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet() -> CSV {
+        CSV::from_string("test.csv", "A,B,C\n1,2,= CALC A1 + B1\n3,4,= SUM A1 A2\n").unwrap()
+    }
+
+    #[test]
+    fn invalid_cell_pointers_are_errors() {
+        let csv = sheet();
+        for pointer in ["", "$", "é1", "A", "Aé", "A0", "D1", "A3"] {
+            assert!(matches!(csv.get(pointer), Err(CsvError::CellPError(_))), "{:?}", pointer);
+            assert!(matches!(csv.get_cell_value(pointer), Err(CsvError::CellPError(_))), "{:?}", pointer);
+            assert!(csv.eval_cell(pointer).is_err(), "{:?}", pointer);
         }
-    })
+
+        let mut csv = sheet();
+        assert!(matches!(csv.set("", "1"), Err(CsvError::CellPError(_))));
+        assert!(matches!(csv.set("é1", "1"), Err(CsvError::CellPError(_))));
+    }
+
+    #[test]
+    fn formulas_reading_past_the_last_row_fail() {
+        let mut csv = sheet();
+        assert!(matches!(csv.eval_str("CALC A3 + 1"), Err(CsvError::CellPError(_))));
+        assert!(matches!(csv.eval_str("SUM A1 A3"), Err(CsvError::CellPError(_))));
+
+        // (CALC returns the error of its first failing operand instead of evaluating the operands before it)
+        assert!(matches!(csv.eval_str("CALC 3 + 4 A3"), Err(CsvError::CellPError(_))));
+        assert!(matches!(csv.eval_str("CALC 3 + 4 x"), Err(CsvError::CellPError(_))));
+        csv.set("B2", "y").unwrap();
+        assert!(matches!(csv.eval_str("CALC A2 * B2"), Err(CsvError::ExprError(_))));
+    }
+
+    #[test]
+    fn items_with_commas_are_rejected() {
+        let mut csv = sheet();
+        assert!(matches!(csv.set("A1", "1,2"), Err(CsvError::RunError(_))));
+        assert!(matches!(csv.update("A1", "x\ny"), Err(CsvError::RunError(_))));
+        assert_eq!(csv.get("A1").unwrap(), "1");
+
+        let mut source = Vec::new();
+        csv.write_source(&mut source).unwrap();
+        assert_eq!(String::from_utf8(source).unwrap(), "A,B,C\n1,2,= CALC A1 + B1\n3,4,= SUM A1 A2\n");
+    }
+
+    #[test]
+    fn strict_errors_match_the_diagnostics() {
+        let csv = CSV::from_string("test.csv", "A,B\nx,= CALC 1 + A1\n2,= CALC A2 * 2\n").unwrap();
+        let diagnostics = check(&csv);
+
+        let Err(CsvError::StrictError(errors)) = check_strict(&csv) else { panic!("the sheet has a failing cell") };
+        assert_eq!(errors.len(), diagnostics.len());
+        assert_eq!(errors[0].0, diagnostics[0].cell);
+        assert_eq!(errors[0].1.get_message(), diagnostics[0].error.get_message());
+        assert_eq!(errors[0].1.get_exit_code(), 4);
+
+        // (Words that aren't pointers or functions are reported the same way)
+        let csv = CSV::from_string("test.csv", "A,B\n1,= CALC A_ + 1\n2,= SUMM A1 A2\n").unwrap();
+        let diagnostics = check(&csv);
+        let Err(CsvError::StrictError(errors)) = check_strict(&csv) else { panic!("the sheet has failing cells") };
+        let messages: Vec<String> = errors.iter().map(|(_, err)| err.get_message()).collect();
+        assert_eq!(messages, diagnostics.iter().map(|diagnostic| diagnostic.error.get_message()).collect::<Vec<_>>());
+        assert_eq!(messages, vec!["Invalid cell pointer A_...", "Unknown function SUMM..."]);
+    }
+
+    #[test]
+    fn body_can_be_read_and_replaced() {
+        let mut csv = sheet();
+        assert_eq!(csv.get_body()[1], vec!["3", "4", "= SUM A1 A2"]);
+
+        csv.recalculate();
+        csv.set_body(vec![vec!["5".to_string(), "6".to_string(), "= CALC A1 + B1".to_string()]]);
+        assert_eq!(csv.get_row_count(), 1);
+        assert_eq!(csv.get_cell_value("C1").unwrap(), "11");
+    }
+
+    #[test]
+    fn updates_below_the_last_row_recalculate_the_new_rows() {
+        let mut csv = CSV::from_string("test.csv", "A,B:= CALC A# * 2,C\n1,,= CALC A4 + 1\n2,,\n").unwrap();
+        csv.recalculate();
+        assert!(matches!(csv.get_cell_value("C1"), Err(CsvError::CellPError(_))));
+
+        // (The column formula fills the new rows and C1 reads the new row)
+        assert_eq!(csv.update("A5", "3").unwrap(), vec!["C1", "B3", "B4", "A5", "B5"]);
+        // (A4 exists now but is still empty)
+        assert!(matches!(csv.get_cell_value("C1"), Err(CsvError::ExprError(_))));
+        assert_eq!(csv.get_cell_value("B5").unwrap(), "6");
+        assert_eq!(csv.update("A4", "4").unwrap(), vec!["C1", "A4", "B4"]);
+        assert_eq!(csv.get_cell_value("C1").unwrap(), "5");
+    }
+
+    #[test]
+    fn sparse_sheets_evaluate_like_columnar_sheets() {
+        let source = "A,B,C:= CALC A# * 2\n1,x,\n2,= CALC A1 + A2,\n";
+        let mut columnar = CSV::from_string("test.csv", source).unwrap();
+        let mut sparse = CSV::from_string("test.csv", source).unwrap();
+        sparse.set("A9000", "5").unwrap();
+        sparse.set("A9000", "").unwrap();
+        assert!(!columnar.is_sparse() && sparse.is_sparse());
+
+        columnar.recalculate();
+        sparse.recalculate();
+        (0..columnar.get_row_count()).for_each(|y| assert_eq!(sparse.get_output_row(y), columnar.get_output_row(y)));
+        assert_eq!(sparse.update("A2", "4").unwrap(), vec!["A2", "B2", "C2"]);
+        assert_eq!(sparse.get_cell_value("B2").unwrap(), "5");
+    }
+
+    #[test]
+    fn columns_can_have_non_ascii_names() {
+        let csv = CSV::from_string("test.csv", "é,B\n2,= CALC é1 * 3\n").unwrap();
+        assert_eq!(csv.get_cell_value("é1").unwrap(), "2");
+        assert_eq!(csv.eval_cell("B1").unwrap(), Value::Number(6.0));
+    }
+
+    #[test]
+    fn column_formulas_apply_to_empty_cells() {
+        let csv = CSV::from_string("test.csv", "A,B,C:= CALC A# * B#\n2,3,\n4,5,= CALC 1 + 1\n6,7\n").unwrap();
+        assert_eq!(csv.get("C1").unwrap(), "= CALC A1 * B1");
+        assert_eq!(csv.get_cell_value("C1").unwrap(), "6");
+        // (Cells with their own content keep it, short rows get the formula too)
+        assert_eq!(csv.get_cell_value("C2").unwrap(), "2");
+        assert_eq!(csv.get_cell_value("C3").unwrap(), "42");
+        assert_eq!(csv.get_range_values("C1", "C3").unwrap(), vec!["6", "2", "42"]);
+
+        let mut source = Vec::new();
+        csv.write_source(&mut source).unwrap();
+        assert_eq!(String::from_utf8(source).unwrap(), "A,B,C:= CALC A# * B#\n2,3,\n4,5,= CALC 1 + 1\n6,7\n");
+    }
+
+    #[test]
+    fn sheets_are_built_and_evaluated_in_memory() {
+        let mut csv = CSV::from_rows("memory.csv", vec!["A".to_string(), "B".to_string()], vec![vec!["2".to_string(), "x".to_string()]]);
+        csv.set("B3", "= CALC A1 * 4").unwrap();
+        assert_eq!(csv.get_row_count(), 3);
+        assert_eq!(csv.get("A2").unwrap(), "");
+
+        assert_eq!(csv.eval_cell("A1").unwrap(), Value::Number(2.0));
+        assert_eq!(csv.eval_cell("B1").unwrap(), Value::Text("x".to_string()));
+        assert_eq!(csv.eval_cell("A2").unwrap(), Value::Empty);
+        assert_eq!(csv.get_cell_number("B3").unwrap(), Some(8.0));
+        assert_eq!(csv.eval_str("CALC A1 + B3").unwrap(), csv.eval_str("= CALC 10").unwrap());
+        assert!(matches!(csv.eval_cell("B5"), Err(CsvError::CellPError(_))));
+    }
+
+    #[test]
+    fn updates_recalculate_their_dependents() {
+        let mut csv = CSV::from_string("test.csv", "A,B,C\n1,= CALC A1 * 2,= CALC B1 + 1\n5,= IF A1 > 0 THEN 1 ELSE 0,x\n").unwrap();
+        // (B2 depends on A1 too, but its value stays the same and isn't reported)
+        assert_eq!(csv.update("A1", "2").unwrap(), vec!["A1", "B1", "C1"]);
+        assert_eq!(csv.get_cell_value("C1").unwrap(), "5");
+        assert_eq!(csv.update("C2", "y").unwrap(), vec!["C2"]);
+
+        // Replaced formulas drop their old references:
+        assert_eq!(csv.update("B1", "= CALC A2 * 2").unwrap(), vec!["B1", "C1"]);
+        assert_eq!(csv.update("A1", "4").unwrap(), vec!["A1"]);
+        assert_eq!(csv.get_cell_value("C1").unwrap(), "11");
+    }
+
+    #[test]
+    fn parallel_evaluation_matches_sequential_evaluation() {
+        // Chains of dependent rows next to independent cells and a failing cell:
+        let mut source = String::from("A,B,C\n1,= CALC A1 * 2,= CALC B1 + x\n");
+        (2..=200).for_each(|y| source.push_str(&format!("{},= CALC A{} + B{},= SUM B1 B{}\n", y, y, y - 1, y)));
+
+        let mut sequential = CSV::from_string("test.csv", &source).unwrap();
+        sequential.recalculate_with_threads(1);
+        let mut parallel = CSV::from_string("test.csv", &source).unwrap();
+        parallel.recalculate_with_threads(8);
+
+        let outputs: Vec<Vec<Vec<String>>> = [&sequential, &parallel].iter()
+            .map(|csv| (0..csv.get_row_count()).map(|y| csv.get_output_row(y)).collect())
+            .collect();
+        assert_eq!(outputs[0], outputs[1]);
+        assert_eq!(parallel.get_cell_value("B200").unwrap(), "20101");
+
+        // (Later edits use the same amount of threads)
+        assert_eq!(parallel.update("A1", "2").unwrap().len(), 400);
+        assert_eq!(parallel.get_cell_value("B200").unwrap(), "20103");
+    }
+
+    #[test]
+    fn strict_outputs_are_only_written_without_errors() {
+        let path = std::env::temp_dir().join(format!("echcell_{}_strict.csv", std::process::id()));
+        let file = path.display().to_string();
+        let output = get_output_path(&file);
+        let _ = fs::remove_file(&output);
+
+        let mut csv = CSV::from_string(&file, "A,B,C\n1,= CALC A1 + x,= SUM A1 Q1\n2,= CALC A2 * 2,\n").unwrap();
+        let Err(CsvError::StrictError(errors)) = generate_output_strict(&csv) else { panic!("2 cells fail") };
+        let cells: Vec<&str> = errors.iter().map(|(cell, _)| cell.as_str()).collect();
+        assert_eq!(cells, vec!["B1", "C1"]);
+        assert_eq!(errors[1].1.get_exit_code(), 7);
+        assert!(!output.exists());
+
+        csv.set("B1", "3").unwrap();
+        csv.set("C1", "").unwrap();
+        generate_output_strict(&csv).unwrap();
+        assert_eq!(fs::read_to_string(&output).unwrap(), "A,B,C\n1,3,#[NULL]\n2,4,#[NULL]\n");
+        fs::remove_file(output).unwrap();
+    }
 }
@@ -0,0 +1,209 @@
+use std::fs;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process;
+use crate::error::CsvError;
+
+/// OVERWRITE
+/// What happens to a file that already exists when it is written again.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Overwrite {
+    /// The file is replaced.
+    #[default]
+    Replace,
+    /// Nothing is written and a CsvError::FileError() is returned.
+    NoClobber,
+    /// The old file is kept next to the new one with a ".bak" suffix ("out_data.csv.bak").
+    Backup,
+}
+
+/// Writes a file atomically: everything is written to a temporary file in the same directory,
+/// which replaces the file (by renaming it) only once it was written completely.
+/// If writing fails the temporary file is removed and the file is left untouched.
+/// A replaced file keeps its permissions.
+/// Returns a Result type of () or a CsvError::FileError() (or the CsvError of the writing function).
+pub fn write_atomic(path: &Path, overwrite: Overwrite, write: impl FnOnce(&mut BufWriter<fs::File>) -> Result<(), CsvError>) -> Result<(), CsvError> {
+    let exists = path.exists();
+    if exists && overwrite == Overwrite::NoClobber {
+        return Err(CsvError::FileError(format!("{} already exists...", path.display())));
+    }
+
+    // Creating the temporary file:
+    let temp_path = get_temp_path(path);
+    let mut temp_file = match fs::File::create(&temp_path) {
+        Ok(f)  => BufWriter::new(f),
+        Err(_) => return Err(CsvError::FileError(format!("Could not create {}...", path.display()))),
+    };
+
+    // Writing it (the temporary file is removed if anything fails):
+    let written = write(&mut temp_file).and_then(|_| match temp_file.into_inner() {
+        Ok(file) => match file.sync_all() {
+            Ok(_)  => Ok(()),
+            Err(_) => Err(CsvError::FileError(format!("Could not write to {}...", path.display()))),
+        },
+        Err(_) => Err(CsvError::FileError(format!("Could not write to {}...", path.display()))),
+    });
+    if let Err(err) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err);
+    }
+
+    // Keeping the old file:
+    if exists && overwrite == Overwrite::Backup {
+        let mut backup_path = path.as_os_str().to_owned();
+        backup_path.push(".bak");
+        if fs::copy(path, &backup_path).is_err() {
+            let _ = fs::remove_file(&temp_path);
+            return Err(CsvError::FileError(format!("Could not create backup file of {}...", path.display())));
+        }
+    }
+
+    // The new file keeps the permissions of the old one:
+    // (the temporary file was created with the default ones)
+    if let Ok(metadata) = fs::metadata(path) {
+        let _ = fs::set_permissions(&temp_path, metadata.permissions());
+    }
+
+    // Replacing the file:
+    // (Without clobbering, the file is linked instead, which fails if a file was created there in the meantime)
+    let replaced = match overwrite {
+        Overwrite::NoClobber => link_new(&temp_path, path),
+        _ => fs::rename(&temp_path, path),
+    };
+    let _ = fs::remove_file(&temp_path);
+
+    match replaced {
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => Err(CsvError::FileError(format!("{} already exists...", path.display()))),
+        Err(_) => Err(CsvError::FileError(format!("Could not replace {}...", path.display()))),
+    }
+}
+
+/// Creates a file from a temporary file without replacing an existing file (the temporary file is left in place).
+/// Returns an io::Error of the kind `AlreadyExists` if the file exists.
+fn link_new(temp_path: &Path, path: &Path) -> io::Result<()> {
+    match fs::hard_link(temp_path, path) {
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => Err(err),
+        // File systems without hard links: the file is created exclusively and filled (not atomic, but never replaced):
+        Err(_) => {
+            let mut file = fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+            let copied = fs::File::open(temp_path).and_then(|mut temp_file| io::copy(&mut temp_file, &mut file));
+            if let Err(err) = copied {
+                let _ = fs::remove_file(path);
+                return Err(err);
+            }
+            file.sync_all()
+        },
+    }
+}
+
+/// Returns the path of the temporary file of a file: a hidden file in the same directory
+/// (renaming only is atomic inside 1 file system), named after the file and the process.
+fn get_temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Returns a path in the temp directory that doesn't exist yet.
+    fn get_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("echcell_{}_{}", process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    /// Writes a text atomically.
+    fn write_text(path: &Path, overwrite: Overwrite, text: &str) -> Result<(), CsvError> {
+        write_atomic(path, overwrite, |file| file.write_all(text.as_bytes()).map_err(|_| CsvError::FileError("write".to_string())))
+    }
+
+    #[test]
+    fn files_are_replaced_or_kept() {
+        let path = get_path("atomic.csv");
+        write_text(&path, Overwrite::NoClobber, "1").unwrap();
+        assert!(matches!(write_text(&path, Overwrite::NoClobber, "2"), Err(CsvError::FileError(_))));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1");
+
+        write_text(&path, Overwrite::Backup, "3").unwrap();
+        let backup_path = PathBuf::from(format!("{}.bak", path.display()));
+        assert_eq!(fs::read_to_string(&backup_path).unwrap(), "1");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "3");
+        assert!(!get_temp_path(&path).exists());
+
+        fs::remove_file(backup_path).unwrap();
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn files_created_while_writing_are_not_clobbered() {
+        let path = get_path("atomic_race.csv");
+        let result = write_atomic(&path, Overwrite::NoClobber, |file| {
+            fs::write(&path, "other").unwrap();
+            file.write_all(b"1").map_err(|_| CsvError::FileError("write".to_string()))
+        });
+
+        assert!(matches!(result, Err(CsvError::FileError(_))));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "other");
+        assert!(!get_temp_path(&path).exists());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn failed_writes_leave_the_file_untouched() {
+        let path = get_path("atomic_failed.csv");
+        write_text(&path, Overwrite::Replace, "1").unwrap();
+
+        let result = write_atomic(&path, Overwrite::Replace, |file| {
+            file.write_all(b"2").unwrap();
+            Err(CsvError::ArgError)
+        });
+        assert!(matches!(result, Err(CsvError::ArgError)));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1");
+        assert!(!get_temp_path(&path).exists());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn backups_are_only_made_of_existing_files() {
+        let path = get_path("atomic_new.csv");
+        let backup_path = PathBuf::from(format!("{}.bak", path.display()));
+        let _ = fs::remove_file(&backup_path);
+
+        write_text(&path, Overwrite::Backup, "1").unwrap();
+        assert!(!backup_path.exists());
+        // (Only the last old file is kept)
+        write_text(&path, Overwrite::Backup, "2").unwrap();
+        write_text(&path, Overwrite::Backup, "3").unwrap();
+        assert_eq!(fs::read_to_string(&backup_path).unwrap(), "2");
+
+        fs::remove_file(backup_path).unwrap();
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn missing_directories_are_file_errors() {
+        let path = std::env::temp_dir().join(format!("echcell_{}_missing", process::id())).join("out.csv");
+        let result = write_text(&path, Overwrite::Replace, "1");
+        assert!(matches!(result, Err(CsvError::FileError(_))));
+        assert!(!path.exists() && !get_temp_path(&path).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn replaced_files_keep_their_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = get_path("atomic_mode.csv");
+        write_text(&path, Overwrite::Replace, "1").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        write_text(&path, Overwrite::Replace, "2").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::BTreeSet;
+use crate::csv::CSV;
+use crate::error::CsvError;
+use super::diagnostic::get_json_string;
+use super::graph::{Cell, DepGraph};
+
+/// A node of an exported graph: the cell pointer and the formula (None if the cell isn't an expression).
+type Node = (String, Option<String>);
+
+impl CSV {
+    /// Returns the cells an expression reads (its precedents): only the ones written in the expression if `transitive` is false,
+    /// also the ones read by those cells (and so on) if it's true.
+    /// Like the dependency graph, only cells of the same sheet are tracked.
+    /// Returns a Result type of the cell pointers ordered by rows or a CsvError::CellPError() if the cell pointer is invalid.
+    pub fn get_precedents(&self, cell_pointer: &str, transitive: bool) -> Result<Vec<String>, CsvError> {
+        let cell = (self.get_column_cor(cell_pointer)?, self.get_row_cor(cell_pointer)?);
+
+        let cells: Vec<Cell> = self.with_graph(|graph| {
+            if transitive {
+                graph.get_all_precedents(cell).into_iter().collect()
+            }
+            else {
+                graph.get_precedents(cell).to_vec()
+            }
+        });
+        Ok(self.get_cell_pointers(cells))
+    }
+
+    /// Returns the cells whose expressions read a cell (its dependents): the ones that read it directly if `transitive` is false,
+    /// every cell whose value changes when the cell changes if it's true.
+    /// Returns a Result type of the cell pointers ordered by rows or a CsvError::CellPError() if the cell pointer is invalid.
+    pub fn get_dependents(&self, cell_pointer: &str, transitive: bool) -> Result<Vec<String>, CsvError> {
+        let cell = (self.get_column_cor(cell_pointer)?, self.get_row_cor(cell_pointer)?);
+
+        let cells: Vec<Cell> = self.with_graph(|graph| {
+            if transitive {
+                let mut affected = graph.get_affected(&[cell]);
+                // (The cell itself only counts if it reads itself through a cycle)
+                if !graph.get_all_precedents(cell).contains(&cell) {
+                    affected.remove(&cell);
+                }
+                affected.into_iter().collect()
+            }
+            else {
+                graph.get_dependents(cell).to_vec()
+            }
+        });
+        Ok(self.get_cell_pointers(cells))
+    }
+
+    /// Returns the dependency graph of the sheet in the Graphviz DOT format.
+    /// Every edge points from a cell to an expression that reads it; expressions are labeled with their formula:
+    /// ```text
+    /// digraph "data.csv" {
+    ///     "C1" [label="C1\n= CALC A1 * B1"];
+    ///     "A1" -> "C1";
+    ///     "B1" -> "C1";
+    /// }
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let (nodes, edges) = self.get_graph_parts();
+
+        let mut lines = vec![format!("digraph {} {{", get_dot_string(&self.file))];
+        nodes.iter().for_each(|(cell, formula)| match formula {
+            Some(formula) => lines.push(format!("    {} [label={}];", get_dot_string(cell), get_dot_string(&format!("{}\n{}", cell, formula)))),
+            None          => lines.push(format!("    {};", get_dot_string(cell))),
+        });
+        edges.iter().for_each(|(from, to)| lines.push(format!("    {} -> {};", get_dot_string(from), get_dot_string(to))));
+        lines.push("}".to_string());
+
+        lines.join("\n")
+    }
+
+    /// Returns the dependency graph of the sheet as a JSON object (`formula` is null for cells that aren't expressions):
+    /// ```text
+    /// {"file":"data.csv","nodes":[{"cell":"A1","formula":null},{"cell":"C1","formula":"= CALC A1 * B1"}],"edges":[{"from":"A1","to":"C1"}]}
+    /// ```
+    pub fn to_graph_json(&self) -> String {
+        let (nodes, edges) = self.get_graph_parts();
+
+        let nodes: Vec<String> = nodes.iter()
+            .map(|(cell, formula)| format!(
+                "{{\"cell\":{},\"formula\":{}}}",
+                get_json_string(cell),
+                formula.as_deref().map_or("null".to_string(), get_json_string),
+            ))
+            .collect();
+        let edges: Vec<String> = edges.iter()
+            .map(|(from, to)| format!("{{\"from\":{},\"to\":{}}}", get_json_string(from), get_json_string(to)))
+            .collect();
+
+        format!("{{\"file\":{},\"nodes\":[{}],\"edges\":[{}]}}", get_json_string(&self.file), nodes.join(","), edges.join(","))
+    }
+
+    /// Calls a function with the dependency graph of the sheet.
+    /// The graph of `recalculate` is used if there is one, otherwise it's built for the call.
+    fn with_graph<R>(&self, f: impl FnOnce(&DepGraph) -> R) -> R {
+        match &self.calc {
+            Some(calc) => f(&calc.graph),
+            None       => f(&DepGraph::build(self, &self.get_expression_cells(), 1)),
+        }
+    }
+
+    /// Returns the nodes (cell pointer and formula of expressions) and the edges (precedent, dependent) of the dependency graph,
+    /// both ordered by rows.
+    fn get_graph_parts(&self) -> (Vec<Node>, Vec<(String, String)>) {
+        let mut edges: Vec<(Cell, Cell)> = self.with_graph(DepGraph::get_edges);
+        edges.sort_unstable_by_key(|(from, to)| (to.1, to.0, from.1, from.0));
+
+        let cells: BTreeSet<Cell> = self.get_expression_cells().into_iter()
+            .chain(edges.iter().flat_map(|(from, to)| [*from, *to]))
+            .map(|(x, y)| (y, x))
+            .collect();
+
+        let nodes = cells.into_iter()
+            .map(|(y, x)| {
+                let formula = self.get_cell_source(x, y);
+                (self.get_cell_pointer(x, y), if formula.starts_with('=') { Some(formula) } else { None })
+            })
+            .collect();
+        let edges = edges.into_iter()
+            .map(|(from, to)| (self.get_cell_pointer(from.0, from.1), self.get_cell_pointer(to.0, to.1)))
+            .collect();
+
+        (nodes, edges)
+    }
+
+    /// Returns the cell pointers of the given cells ordered by rows.
+    fn get_cell_pointers(&self, mut cells: Vec<Cell>) -> Vec<String> {
+        cells.sort_unstable_by_key(|(x, y)| (*y, *x));
+        cells.into_iter().map(|(x, y)| self.get_cell_pointer(x, y)).collect()
+    }
+}
+
+/// Returns a text as a quoted DOT string.
+fn get_dot_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet() -> CSV {
+        CSV::from_string("data.csv", "A,B,C\n1,2,= CALC A1 * B1\n3,= CALC C1 + 1,= SUM B2 B2\n").unwrap()
+    }
+
+    #[test]
+    fn precedents_and_dependents_are_found() {
+        let csv = sheet();
+        assert_eq!(csv.get_precedents("C2", false).unwrap(), vec!["B2"]);
+        assert_eq!(csv.get_precedents("C2", true).unwrap(), vec!["A1", "B1", "C1", "B2"]);
+        assert_eq!(csv.get_dependents("A1", false).unwrap(), vec!["C1"]);
+        assert_eq!(csv.get_dependents("A1", true).unwrap(), vec!["C1", "B2", "C2"]);
+        assert!(csv.get_dependents("A2", true).unwrap().is_empty());
+        assert!(matches!(csv.get_precedents("Q1", true), Err(CsvError::CellPError(_))));
+
+        // (The graph of `recalculate` gives the same answers)
+        let mut calculated = sheet();
+        calculated.recalculate();
+        assert_eq!(calculated.get_precedents("C2", true).unwrap(), csv.get_precedents("C2", true).unwrap());
+    }
+
+    #[test]
+    fn graphs_are_exported() {
+        let csv = CSV::from_string("data.csv", "A,B\n1,= CALC A1 * \"2\"\n").unwrap();
+        assert_eq!(csv.to_dot(), "digraph \"data.csv\" {\n    \"A1\";\n    \"B1\" [label=\"B1\\n= CALC A1 * \\\"2\\\"\"];\n    \"A1\" -> \"B1\";\n}");
+        assert_eq!(
+            csv.to_graph_json(),
+            "{\"file\":\"data.csv\",\"nodes\":[{\"cell\":\"A1\",\"formula\":null},{\"cell\":\"B1\",\"formula\":\"= CALC A1 * \\\"2\\\"\"}],\
+            \"edges\":[{\"from\":\"A1\",\"to\":\"B1\"}]}",
+        );
+    }
+}
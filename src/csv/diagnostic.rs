@@ -0,0 +1,148 @@
+use std::fmt;
+use std::ops::Range;
+use crate::error::CsvError;
+
+/// DIAGNOSTIC
+/// The error of 1 cell together with where it happened: the file, the cell pointer and the formula of the cell.
+/// `span` is the byte range of the formula word that caused the error (None if no single word can be blamed)
+/// and `help` is an optional hint on how to fix it.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub file: String,
+    pub cell: String,
+    pub formula: String,
+    pub span: Option<Range<usize>>,
+    pub error: CsvError,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    /// Renders the diagnostic like a compiler error, with a caret line under the word that caused it:
+    /// ```text
+    /// error[CellPError]: Column index out of bounds...
+    ///   --> data.csv:C2
+    ///    |
+    ///    | = CALC Q1 + A1
+    ///    |        ^^
+    ///    = help: column `Q` does not exist; header has A–D
+    /// ```
+    pub fn render(&self) -> String {
+        let mut lines = vec![
+            format!("error[{}]: {}", self.error.get_kind(), self.error.get_message()),
+            format!("  --> {}:{}", self.file, self.cell),
+            "   |".to_string(),
+            format!("   | {}", self.formula),
+        ];
+
+        // (Carets are placed by characters, not bytes)
+        if let Some(span) = &self.span {
+            let start = self.formula[..span.start].chars().count();
+            let len = self.formula[span.clone()].chars().count().max(1);
+            lines.push(format!("   | {}{}", " ".repeat(start), "^".repeat(len)));
+        }
+
+        if let Some(help) = &self.help {
+            lines.push(format!("   = help: {}", help));
+        }
+
+        lines.join("\n")
+    }
+
+    /// Returns the diagnostic as a JSON object on 1 line (`kind` is the name of the CsvError variant, `span` is a byte range of `formula`):
+    /// ```text
+    /// {"type":"diagnostic","file":"data.csv","cell":"A4","severity":"error","kind":"CellPError","message":"Column index out of bounds...","formula":"= CALC Q1 + A1","span":{"start":7,"end":9},"help":"..."}
+    /// ```
+    pub fn to_json(&self) -> String {
+        let span = match &self.span {
+            Some(span) => format!("{{\"start\":{},\"end\":{}}}", span.start, span.end),
+            None       => "null".to_string(),
+        };
+        let help = match &self.help {
+            Some(help) => get_json_string(help),
+            None       => "null".to_string(),
+        };
+
+        format!(
+            "{{\"type\":\"diagnostic\",\"file\":{},\"cell\":{},\"severity\":\"error\",\"kind\":\"{}\",\"message\":{},\"formula\":{},\"span\":{},\"help\":{}}}",
+            get_json_string(&self.file),
+            get_json_string(&self.cell),
+            self.error.get_kind(),
+            get_json_string(&self.error.get_message()),
+            get_json_string(&self.formula),
+            span,
+            help,
+        )
+    }
+}
+
+/// Returns the JSON object that closes the diagnostics of a run (the amount of checked files and found errors).
+pub fn get_json_summary(files: usize, errors: usize) -> String {
+    format!("{{\"type\":\"summary\",\"files\":{},\"errors\":{},\"warnings\":0}}", files, errors)
+}
+
+/// Returns a text as a quoted JSON string.
+pub(crate) fn get_json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    text.chars().for_each(|c| match c {
+        '"'  => json.push_str("\\\""),
+        '\\' => json.push_str("\\\\"),
+        '\n' => json.push_str("\\n"),
+        '\r' => json.push_str("\\r"),
+        '\t' => json.push_str("\\t"),
+        c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+        c => json.push(c),
+    });
+    json.push('"');
+    json
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic() -> Diagnostic {
+        Diagnostic {
+            file: "data.csv".to_string(),
+            cell: "C2".to_string(),
+            formula: "= CALC é1 + Q1".to_string(),
+            span: Some(12..14),
+            error: CsvError::CellPError("Column index out of bounds...".to_string()),
+            help: Some("column `Q` does not exist; header has A–D".to_string()),
+        }
+    }
+
+    #[test]
+    fn carets_are_placed_under_the_word() {
+        let expected = [
+            "error[CellPError]: Column index out of bounds...",
+            "  --> data.csv:C2",
+            "   |",
+            "   | = CALC é1 + Q1",
+            "   |            ^^",
+            "   = help: column `Q` does not exist; header has A–D",
+        ];
+        assert_eq!(diagnostic().render(), expected.join("\n"));
+
+        let unlocated = Diagnostic { span: None, help: None, ..diagnostic() };
+        assert_eq!(unlocated.render().lines().count(), 4);
+    }
+
+    #[test]
+    fn diagnostics_are_written_as_json() {
+        let json = Diagnostic { formula: "= CALC \"a\"\t+ Q1".to_string(), span: Some(14..16), help: None, ..diagnostic() }.to_json();
+        assert_eq!(
+            json,
+            "{\"type\":\"diagnostic\",\"file\":\"data.csv\",\"cell\":\"C2\",\"severity\":\"error\",\"kind\":\"CellPError\",\
+            \"message\":\"Column index out of bounds...\",\"formula\":\"= CALC \\\"a\\\"\\t+ Q1\",\"span\":{\"start\":14,\"end\":16},\"help\":null}",
+        );
+        assert_eq!(get_json_summary(2, 1), "{\"type\":\"summary\",\"files\":2,\"errors\":1,\"warnings\":0}");
+        assert_eq!(get_json_string("a\u{1}b"), "\"a\\u0001b\"");
+    }
+}
@@ -0,0 +1,146 @@
+use std::fs;
+use std::io::Write;
+use crate::csv::CSV;
+use crate::error::CsvError;
+
+/// Separates the value of an expression from its formula in a dual output ("2002|= CALC A2 * B2").
+pub const DUAL_SEPARATOR: char = '|';
+
+/// Marks the next character of a dual item as part of the text (`a\|b` is the text `a|b`).
+const DUAL_ESCAPE: char = '\\';
+
+/// Returns the item of a cell in a dual output: expressions are written as their value followed by their formula
+/// (`value|= formula`) and cells of a column formula as their value followed by the separator only (`value|`, the formula is in the header).
+/// Every other cell is written as it is in the input file (empty cells stay empty).
+/// Separators and escape characters in values and texts are escaped, so only the separator of the formula is left.
+fn get_dual_item(csv: &CSV, x_cor: usize, y_cor: usize) -> String {
+    let source = csv.get_cell_source(x_cor, y_cor);
+    if !source.starts_with('=') {
+        return escape_dual_text(&source);
+    }
+
+    let value = match csv.get_evaluated_value(x_cor, y_cor) {
+        Ok(val)  => val,
+        Err(err) => err.to_string(),
+    };
+    // (Empty body items have their value from the column formula)
+    let formula = match csv.body.get_str(x_cor, y_cor).is_empty() {
+        true  => String::new(),
+        false => source,
+    };
+    format!("{}{}{}", escape_dual_text(&value), DUAL_SEPARATOR, formula)
+}
+
+/// Escapes every separator and escape character of a text with the escape character.
+fn escape_dual_text(text: &str) -> String {
+    text.chars().fold(String::with_capacity(text.len()), |mut escaped, c| {
+        if c == DUAL_SEPARATOR || c == DUAL_ESCAPE {
+            escaped.push(DUAL_ESCAPE);
+        }
+        escaped.push(c);
+        escaped
+    })
+}
+
+/// Returns the input item of a cell of a dual output (the reverse of `get_dual_item`):
+/// the formula of `value|= formula`, an empty item for `value|` (the column formula applies again)
+/// and the unescaped text of any other item.
+pub fn strip_dual_item(item: &str) -> String {
+    let mut text = String::with_capacity(item.len());
+    let mut chars = item.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            DUAL_ESCAPE => text.extend(chars.next().map(|(_, c)| c)),
+            // (The first separator that isn't escaped is followed by the formula, if the value was produced by echcell)
+            DUAL_SEPARATOR => {
+                let formula = &item[i + c.len_utf8()..];
+                if formula.is_empty() || formula.starts_with('=') {
+                    return formula.to_string();
+                }
+                text.push(c);
+            },
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+/// Writes the evaluated CSV object to a writer as a dual output: the values of the expressions are kept together with their formulas,
+/// so the output shows the results and can still be evaluated again (`CSV::from_dual_file` loads the formulas back).
+/// The header keeps its column formulas; cells of column formulas are only written with their value.
+/// Returns a Result type of () or a CsvError::FileError() if writing failed.
+pub fn write_output_dual(csv: &CSV, writer: &mut impl Write) -> Result<(), CsvError> {
+    let mut buffer = csv.get_source_header().join(",");
+    (0..csv.get_row_count()).for_each(|y| {
+        let row: Vec<String> = (0..csv.get_row_width(y)).map(|x| get_dual_item(csv, x, y)).collect();
+        buffer.push('\n');
+        buffer.push_str(&row.join(","));
+    });
+    buffer.push('\n');
+
+    match writer.write_all(buffer.as_bytes()) {
+        Ok(_)  => Ok(()),
+        Err(_) => Err(CsvError::FileError("Could not write to output file...".to_string())),
+    }
+}
+
+impl CSV {
+    /// Creates a new CSV object from the contents of a dual output: every `value|= formula` cell becomes its formula again (see `strip_dual_item`).
+    /// The name is used like a file path (see `from_string`).
+    pub fn from_dual_string(name: &str, contents: &str) -> Result<Self, CsvError> {
+        let (header, body) = Self::parse_contents(contents)?;
+        let body = body.into_iter()
+            .map(|row| row.iter().map(|item| strip_dual_item(item)).collect())
+            .collect();
+
+        Ok(Self::from_rows(name, header, body))
+    }
+
+    /// Creates a new CSV object from a dual output file (see `from_dual_string`).
+    pub fn from_dual_file(path: &str) -> Result<Self, CsvError> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Self::from_dual_string(path, &contents),
+            Err(_)       => Err(CsvError::FileError("Could not read csv file...".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the dual output of an evaluated sheet.
+    fn get_dual_output(csv: &CSV) -> String {
+        let mut output = Vec::new();
+        write_output_dual(csv, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn dual_outputs_are_read_back() {
+        let source = "t,a,b:= CALC a# * 2,c\nx|=y,2,,= CALC a1 + a2\nback\\slash,3,,\n";
+        let mut csv = CSV::from_string("dual.csv", source).unwrap();
+        csv.recalculate();
+
+        let output = get_dual_output(&csv);
+        assert_eq!(output, "t,a,b:= CALC a# * 2,c\nx\\|=y,2,4|,5|= CALC a1 + a2\nback\\\\slash,3,6|,\n");
+
+        let mut loaded = CSV::from_dual_string("dual.csv", &output).unwrap();
+        assert_eq!(loaded.get_source_header(), csv.get_source_header());
+        assert_eq!(loaded.get_body(), csv.get_body());
+
+        loaded.recalculate();
+        assert_eq!(get_dual_output(&loaded), output);
+    }
+
+    #[test]
+    fn only_produced_cells_are_stripped() {
+        assert_eq!(strip_dual_item("6|= CALC A1 * B1"), "= CALC A1 * B1");
+        assert_eq!(strip_dual_item("a\\|b|= CALC A1"), "= CALC A1");
+        assert_eq!(strip_dual_item("6|"), "");
+        // (Texts typed into the output without escaping stay as they are)
+        assert_eq!(strip_dual_item("a|b"), "a|b");
+        assert_eq!(strip_dual_item("a\\|=b"), "a|=b");
+    }
+}
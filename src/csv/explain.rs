@@ -0,0 +1,233 @@
+use std::cell::RefCell;
+use std::fmt;
+use crate::csv::{CSV, EVAL_STACK};
+use crate::error::CsvError;
+use super::exprs::{Token, Functions, eval_expr};
+use super::graph::get_references;
+use super::refs::CellRef;
+
+thread_local! {
+    /// Steps recorded while a cell is explained, with the depth of `EVAL_STACK` the explained cell is evaluated at
+    /// (the evaluations of the cells it references are deeper and aren't recorded).
+    static TRACE: RefCell<Option<(usize, Vec<String>)>> = const { RefCell::new(None) };
+}
+
+/// Records an evaluation step of the explained cell (does nothing if no cell is being explained).
+pub(crate) fn record(step: impl FnOnce() -> String) {
+    TRACE.with(|trace| {
+        if let Some((depth, steps)) = trace.borrow_mut().as_mut() {
+            if EVAL_STACK.with(|stack| stack.borrow().len()) == *depth {
+                steps.push(step());
+            }
+        }
+    });
+}
+
+/// EXPLANATION
+/// How the value of 1 cell was evaluated: its tokens, the structure of its expression,
+/// the values of the cells it references and every step of the evaluation (IF conditions, CALC stack...).
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub cell: String,
+    pub formula: String,
+    pub tokens: Vec<Token>,
+    pub structure: Vec<String>,
+    pub references: Vec<(String, Result<String, CsvError>)>,
+    pub steps: Vec<String>,
+    pub value: Result<String, CsvError>,
+}
+
+impl CSV {
+    /// Evaluates a cell again and records how its value was found (see `Explanation`).
+    /// Cells that don't hold an expression are explained by their text.
+    /// Returns a Result type of Explanation or a CsvError::CellPError() if the cell pointer is invalid.
+    pub fn explain(&self, cell_pointer: &str) -> Result<Explanation, CsvError> {
+        let x_cor = self.get_column_cor(cell_pointer)?;
+        let y_cor = self.get_row_cor(cell_pointer)?;
+        let cell = self.get_cell_pointer(x_cor, y_cor);
+        let formula = self.get_cell_source(x_cor, y_cor);
+
+        if !formula.starts_with('=') {
+            return Ok(Explanation {
+                cell, tokens: Vec::new(), structure: Vec::new(), references: Vec::new(), steps: Vec::new(), value: Ok(formula.clone()), formula,
+            });
+        }
+
+        let tokens = Token::tokenize(&formula[1..]);
+        let words: Vec<&str> = formula[1..].split_whitespace().collect();
+        let structure = get_structure(&tokens, &words, 0);
+        let references = self.get_reference_values(&formula, &tokens);
+
+        // Evaluating the cell (like `get_evaluated_value`, but never from the cache) while the trace records its steps:
+        let key = (self.key.clone(), x_cor, y_cor);
+        let depth = EVAL_STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            stack.push(key);
+            stack.len()
+        });
+        TRACE.with(|trace| *trace.borrow_mut() = Some((depth, Vec::new())));
+        let value = eval_expr(&formula, self);
+        let steps = TRACE.with(|trace| trace.borrow_mut().take()).map(|(_, steps)| steps).unwrap_or_default();
+        EVAL_STACK.with(|stack| stack.borrow_mut().pop());
+
+        Ok(Explanation { cell, formula, tokens, structure, references, steps, value })
+    }
+
+    /// Returns the values of the cells an expression references.
+    /// Cells of this sheet are listed 1 by 1 (also inside ranges), cells and ranges of linked files as they're written.
+    fn get_reference_values(&self, formula: &str, tokens: &[Token]) -> Vec<(String, Result<String, CsvError>)> {
+        let local = get_references(formula, self).into_iter()
+            .map(|(x, y)| (self.get_cell_pointer(x, y), self.get_evaluated_value(x, y)));
+
+        let linked = tokens.iter().filter_map(|token| match token {
+            Token::Cell(pointer) if pointer.contains('!') => Some((pointer.clone(), self.get_cell_value(pointer))),
+            Token::Range(start, end) if start.contains('!') => {
+                let values = self.get_range_values(start, end).map(|values| values.join(", "));
+                Some((format!("{}:{}", start, end.split_once('!').map_or(end.as_str(), |(_, end)| end)), values))
+            },
+            _ => None,
+        });
+
+        local.chain(linked).collect()
+    }
+}
+
+/// Describes the structure of an expression (1 line per part, nested parts are indented).
+fn get_structure(tokens: &[Token], words: &[&str], depth: usize) -> Vec<String> {
+    let indent = "  ".repeat(depth);
+    let rest = words.get(1..).unwrap_or_default().join(" ");
+
+    match tokens.first() {
+        Some(Token::Func(Functions::If)) => {
+            // (The parts are split at THEN and ELSE)
+            let mut parts: Vec<(Vec<Token>, Vec<&str>)> = vec![(Vec::new(), Vec::new())];
+            tokens.iter().zip(words.iter()).skip(1).for_each(|(token, word)| match token {
+                Token::Then | Token::Else => parts.push((Vec::new(), Vec::new())),
+                _ => {
+                    let part = parts.last_mut().unwrap();
+                    part.0.push(token.clone());
+                    part.1.push(word);
+                },
+            });
+
+            let mut lines = vec![format!("{}IF", indent)];
+            lines.push(format!("{}  condition: {}", indent, parts[0].1.join(" ")));
+            ["then", "else"].iter().enumerate().for_each(|(i, name)| match parts.get(i + 1) {
+                Some((tokens, words)) => {
+                    lines.push(format!("{}  {}:", indent, name));
+                    lines.extend(get_structure(tokens, words, depth + 2));
+                },
+                None => lines.push(format!("{}  {}: (missing)", indent, name)),
+            });
+            lines
+        },
+        Some(Token::Func(Functions::Sum)) => vec![format!("{}SUM of the range {}", indent, rest)],
+        Some(Token::Func(Functions::Avg)) => vec![format!("{}AVG of the range {}", indent, rest)],
+        Some(Token::Func(Functions::Calc)) => vec![format!("{}CALC of the math expression {}", indent, rest)],
+        Some(Token::Cell(_) | Token::Number(_)) => vec![format!("{}the value {}", indent, words.join(" "))],
+        Some(_) => vec![format!("{}unknown function {}", indent, words[0])],
+        None => vec![format!("{}(empty)", indent)],
+    }
+}
+
+/// Describes a token the way it's written in the expression, together with its kind ("cell A1", "operator +").
+fn describe_token(token: &Token) -> String {
+    match token {
+        // (Words that aren't cell pointers are texts, like the results of IF branches)
+        Token::Cell(pointer) if CellRef::parse(pointer.split_once('!').map_or(pointer.as_str(), |(_, local)| local)).is_some() => format!("cell {}", pointer),
+        Token::Cell(word)        => format!("text {}", word),
+        Token::Range(start, end) => format!("range {}:{}", start, end),
+        Token::Number(n)         => format!("number {}", n),
+        Token::Operator(op)      => format!("operator {}", op.get_symbol()),
+        Token::CmpOperator(cmp)  => format!("comparison {}", cmp.get_symbol()),
+        Token::Func(func)        => format!("function {}", func.get_name()),
+        Token::Then              => "THEN".to_string(),
+        Token::Else              => "ELSE".to_string(),
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}", self.cell, self.formula)?;
+        if !self.formula.starts_with('=') {
+            return write!(f, "  (not an expression; the value is the text of the cell)");
+        }
+
+        let tokens: Vec<String> = self.tokens.iter().map(describe_token).collect();
+        writeln!(f, "tokens:\n  {}", tokens.join(", "))?;
+        writeln!(f, "structure:")?;
+        self.structure.iter().try_for_each(|line| writeln!(f, "  {}", line))?;
+
+        writeln!(f, "references:")?;
+        if self.references.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        self.references.iter().try_for_each(|(cell, value)| match value {
+            Ok(value) if value.is_empty() => writeln!(f, "  {} = #[NULL]", cell),
+            Ok(value) => writeln!(f, "  {} = {}", cell, value),
+            Err(err)  => writeln!(f, "  {} fails: {}", cell, err),
+        })?;
+
+        writeln!(f, "steps:")?;
+        if self.steps.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        self.steps.iter().enumerate().try_for_each(|(i, step)| writeln!(f, "  {}. {}", i + 1, step))?;
+
+        match &self.value {
+            Ok(value) => write!(f, "result:\n  {}", value),
+            Err(err)  => write!(f, "result:\n  {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explanations_show_the_evaluation() {
+        let csv = CSV::from_string("test.csv", "A,B,C\n2,x,= IF A1 > 1 THEN CALC A1 * 3 ELSE no\n").unwrap();
+        let explanation = csv.explain("C1").unwrap();
+
+        assert_eq!(explanation.references.len(), 1);
+        assert_eq!(explanation.references[0].0, "A1");
+        assert_eq!(explanation.value.as_deref().unwrap(), "6");
+        assert_eq!(explanation.steps[0], "condition 2 > 1 is true");
+        assert_eq!(explanation.steps[1], "IF takes the THEN branch");
+
+        let text = explanation.to_string();
+        assert!(text.contains("tokens:\n  function IF, cell A1, comparison >, number 1, THEN, function CALC, cell A1, operator *, number 3, ELSE, text no\n"), "{}", text);
+        assert!(text.ends_with("result:\n  6"));
+    }
+
+    #[test]
+    fn calc_steps_show_the_stack() {
+        let csv = CSV::from_string("test.csv", "A,B,C:= CALC A# * B# + 1\n2,3,\n").unwrap();
+        let expected = [
+            "C1: = CALC A1 * B1 + 1",
+            "tokens:\n  function CALC, cell A1, operator *, cell B1, operator +, number 1",
+            "structure:\n  CALC of the math expression A1 * B1 + 1",
+            "references:\n  A1 = 2\n  B1 = 3",
+            "steps:\n  1. CALC in postfix form: 2 3 * 1 +\n  2. push 2: stack [2]\n  3. push 3: stack [2, 3]\n  4. 2 * 3 = 6: stack [6]\n  5. push 1: stack [6, 1]\n  6. 6 + 1 = 7: stack [7]",
+            "result:\n  7",
+        ];
+        // (Cells of column formulas are explained with their expanded formula)
+        assert_eq!(csv.explain("C1").unwrap().to_string(), expected.join("\n"));
+    }
+
+    #[test]
+    fn branch_results_are_not_references() {
+        let csv = CSV::from_string("test.csv", "A,B,C\n2,3,= IF A1 > 1 THEN B1 ELSE A9\n").unwrap();
+        let explanation = csv.explain("C1").unwrap();
+        assert_eq!(explanation.references.iter().map(|(cell, _)| cell.as_str()).collect::<Vec<_>>(), vec!["A1"]);
+        assert_eq!(explanation.value.unwrap(), "B1");
+    }
+
+    #[test]
+    fn texts_are_explained_by_themselves() {
+        let csv = CSV::from_string("test.csv", "A,B\n2,x\n").unwrap();
+        assert_eq!(csv.explain("B1").unwrap().to_string(), "B1: x\n  (not an expression; the value is the text of the cell)");
+        assert!(matches!(csv.explain("C1"), Err(CsvError::CellPError(_))));
+    }
+}
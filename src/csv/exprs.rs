@@ -1,4 +1,5 @@
 use crate::csv::CSV;
+use crate::error::CsvError;
 use super::funcs::func_caller;
 
 #[derive(Debug, Clone, Copy)]
@@ -9,6 +10,18 @@ pub enum Functions {
     If,
 }
 
+impl Functions {
+    /// Returns the function as it's written in an expression ("SUM", "IF"...).
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Calc => "CALC",
+            Self::Sum  => "SUM",
+            Self::Avg  => "AVG",
+            Self::If   => "IF",
+        }
+    }
+}
+
 /// BINARY OPERATORS
 /// Mainly used for CALC function.
 #[derive(Debug, Clone, Copy)]
@@ -27,6 +40,16 @@ impl BinaryOp {
             (Self::Mult | Self::Div, Self::Plus | Self::Minus) => true,
             _ => false,       }
     }
+
+    /// Returns the operator as it's written in an expression ("+", "-"...).
+    pub fn get_symbol(&self) -> &'static str {
+        match self {
+            Self::Plus  => "+",
+            Self::Minus => "-",
+            Self::Mult  => "*",
+            Self::Div   => "/",
+        }
+    }
 }
 
 /// COMPARISON OPERATORS
@@ -50,11 +73,23 @@ impl CmpOp {
     pub fn ge(left: f64, right: f64) -> bool { left >= right }
     // Checks whether the left value is smaller or eqal than the right value:
     pub fn le(left: f64, right: f64) -> bool { left <= right }
+
+    /// Returns the operator as it's written in an expression ("==", ">"...).
+    pub fn get_symbol(&self) -> &'static str {
+        match self {
+            Self::Eq => "==",
+            Self::Gt => ">",
+            Self::Lt => "<",
+            Self::Ge => ">=",
+            Self::Le => "<=",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Token {
     Cell(String),
+    Range(String, String),
     Number(f64),
     Operator(BinaryOp),
     CmpOperator(CmpOp),
@@ -67,7 +102,7 @@ pub enum Token {
 
 impl Token {
     /// Tokenizes the input expression.
-    fn tokenize(expr: &String) -> Vec<Self> {
+    pub fn tokenize(expr: &str) -> Vec<Self> {
         let split_expr: Vec<String> = expr.split_whitespace().map(String::from).collect();
         
         split_expr.iter().map(|word| {
@@ -127,6 +162,14 @@ impl Token {
                 Self::Number(n)
             }
 
+            // Ranges written as 1 word ("A1:A5", "rates.csv!A1:A5"):
+            else if let Some((start, end)) = word.split_once(':') {
+                match start.split_once('!') {
+                    Some((file, _)) => Self::Range(start.to_string(), format!("{}!{}", file, end)),
+                    None            => Self::Range(start.to_string(), end.to_string()),
+                }
+            }
+
             else {
                 Self::Cell(word.clone())
             }
@@ -146,38 +189,41 @@ impl Token {
 /// MAIN EVALUATER
 /// Evaluates the input cell.
 /// If the evaluation fails it returns a String with an error message.
-pub fn eval(item: &String, csv: &CSV) -> String {
+pub fn eval(item: &str, csv: &CSV) -> String {
     if item.is_empty() {
         return "#[NULL]".to_string();
     }
 
     // If the cell contains an expression:
-    if &item[0..1] == "=" {
-        //println!("[FOUND EXPR] {}", item);
-
-        // Expression to be tokenized:
-        // (First 2 elements are removed because they are the '= ')
-        let expr: String = item[2..item.len()].to_string();
-
-        // Tokens (tokenizing):
-        let tokens = Token::tokenize(&expr);
-        //println!("[TOKENS] {:?}", tokens);
-        
-        if tokens.is_empty() {
-            return "#[TOKEN ERROR]".to_string();
-        }
-        
-        // Creating arguments vector:
-        let args = &tokens[1..tokens.len()].to_vec();
-        
-        // Caller function:
-        // (Evaluates the functions)
-        match func_caller(&csv, &tokens[0], &args) {
-            Ok(val)  => return val,
-            Err(err) => return err.to_string(),
-        }
+    if item.starts_with('=') {
+        return match eval_expr(item, csv) {
+            Ok(val)  => val,
+            Err(err) => err.to_string(),
+        };
     }
     
     // If it's not a expression:
     item.to_string()
 }
+
+/// Evaluates an expression (an item starting with '=').
+/// Returns a Result type of the value or the CsvError that made the evaluation fail.
+pub fn eval_expr(item: &str, csv: &CSV) -> Result<String, CsvError> {
+    // Expression to be tokenized:
+    // (The first character is removed because it is the '=')
+    let expr = &item[1..item.len()];
+
+    // Tokens (tokenizing):
+    let tokens = Token::tokenize(expr);
+
+    if tokens.is_empty() {
+        return Err(CsvError::TokenError);
+    }
+
+    // Creating arguments vector:
+    let args = &tokens[1..tokens.len()];
+
+    // Caller function:
+    // (Evaluates the functions)
+    func_caller(csv, &tokens[0], args)
+}
@@ -0,0 +1,313 @@
+use std::io::Write;
+use std::path::Path;
+use crate::error::CsvError;
+use super::diagnostic::get_json_string;
+
+/// OUTPUT WRITER
+/// Writes an evaluated sheet in 1 output format: the header first, then every row, then `finish` closes the output.
+/// The rows hold the cells as they're written to an output csv file (empty cells are "#[NULL]",
+/// failing expressions are their error message) and may be shorter than the header.
+/// Implement it to add a format; `write_output_as` and `write_output_streaming_as` accept any writer.
+pub trait OutputWriter {
+    fn write_header(&mut self, writer: &mut dyn Write, header: &[String]) -> Result<(), CsvError>;
+    fn write_row(&mut self, writer: &mut dyn Write, row: &[String]) -> Result<(), CsvError>;
+    fn finish(&mut self, writer: &mut dyn Write) -> Result<(), CsvError>;
+}
+
+/// OUTPUT FORMAT
+/// The built-in output formats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Csv,
+    Json,
+    Markdown,
+    Html,
+    Table,
+}
+
+impl OutputFormat {
+    /// Returns the format with the given name ("csv", "json", "markdown" or "md", "html", "table").
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "csv"             => Some(Self::Csv),
+            "json"            => Some(Self::Json),
+            "markdown" | "md" => Some(Self::Markdown),
+            "html"            => Some(Self::Html),
+            "table"           => Some(Self::Table),
+            _ => None,
+        }
+    }
+
+    /// Returns the format that belongs to the extension of a path (None for unknown extensions).
+    pub fn from_path(path: &str) -> Option<Self> {
+        match Path::new(path).extension()?.to_str()? {
+            "csv"          => Some(Self::Csv),
+            "json"         => Some(Self::Json),
+            "md"           => Some(Self::Markdown),
+            "html" | "htm" => Some(Self::Html),
+            "txt"          => Some(Self::Table),
+            _ => None,
+        }
+    }
+
+    /// Returns the file extension of the format.
+    pub fn get_extension(&self) -> &'static str {
+        match self {
+            Self::Csv      => "csv",
+            Self::Json     => "json",
+            Self::Markdown => "md",
+            Self::Html     => "html",
+            Self::Table    => "txt",
+        }
+    }
+
+    /// Returns a new writer of the format.
+    pub fn get_writer(&self) -> Box<dyn OutputWriter> {
+        match self {
+            Self::Csv      => Box::new(CsvWriter),
+            Self::Json     => Box::new(JsonWriter::default()),
+            Self::Markdown => Box::new(MarkdownWriter::default()),
+            Self::Html     => Box::new(HtmlWriter::default()),
+            Self::Table    => Box::new(TableWriter::default()),
+        }
+    }
+}
+
+/// CSV
+/// The format of `generate_output`: the cells separated by commas.
+pub struct CsvWriter;
+
+impl OutputWriter for CsvWriter {
+    fn write_header(&mut self, writer: &mut dyn Write, header: &[String]) -> Result<(), CsvError> {
+        write_text(writer, &format!("{}\n", header.join(",")))
+    }
+
+    fn write_row(&mut self, writer: &mut dyn Write, row: &[String]) -> Result<(), CsvError> {
+        write_text(writer, &format!("{}\n", row.join(",")))
+    }
+
+    fn finish(&mut self, _writer: &mut dyn Write) -> Result<(), CsvError> {
+        Ok(())
+    }
+}
+
+/// JSON
+/// An array with 1 object per row, keyed by the header (1 object per line).
+/// Numbers in their canonical form ("35", "1.5") are JSON numbers, empty and missing cells are null and everything else is a string.
+#[derive(Default)]
+pub struct JsonWriter {
+    header: Vec<String>,
+    rows: usize,
+}
+
+impl OutputWriter for JsonWriter {
+    fn write_header(&mut self, writer: &mut dyn Write, header: &[String]) -> Result<(), CsvError> {
+        self.header = header.to_vec();
+        write_text(writer, "[")
+    }
+
+    fn write_row(&mut self, writer: &mut dyn Write, row: &[String]) -> Result<(), CsvError> {
+        let fields: Vec<String> = self.header.iter().enumerate()
+            .map(|(x, name)| {
+                let value = match row.get(x).map(String::as_str) {
+                    None | Some("#[NULL]") => "null".to_string(),
+                    // (Only numbers written in their canonical form, so "007" or "1e3" stay as they are)
+                    Some(item) => match item.parse::<f64>() {
+                        Ok(n) if n.is_finite() && n.to_string() == item => n.to_string(),
+                        _ => get_json_string(item),
+                    },
+                };
+                format!("{}:{}", get_json_string(name), value)
+            })
+            .collect();
+
+        let separator = if self.rows == 0 { "\n" } else { ",\n" };
+        self.rows += 1;
+        write_text(writer, &format!("{}  {{{}}}", separator, fields.join(",")))
+    }
+
+    fn finish(&mut self, writer: &mut dyn Write) -> Result<(), CsvError> {
+        write_text(writer, "\n]\n")
+    }
+}
+
+/// MARKDOWN
+/// A Markdown table (`|` inside cells is escaped, empty cells stay empty).
+#[derive(Default)]
+pub struct MarkdownWriter {
+    columns: usize,
+}
+
+impl OutputWriter for MarkdownWriter {
+    fn write_header(&mut self, writer: &mut dyn Write, header: &[String]) -> Result<(), CsvError> {
+        self.columns = header.len();
+        let cells: Vec<String> = header.iter().map(|name| name.replace('|', "\\|")).collect();
+        write_text(writer, &format!("| {} |\n|{}\n", cells.join(" | "), " --- |".repeat(self.columns)))
+    }
+
+    fn write_row(&mut self, writer: &mut dyn Write, row: &[String]) -> Result<(), CsvError> {
+        let cells: Vec<String> = (0..self.columns.max(row.len()))
+            .map(|x| get_display_text(row.get(x)).replace('|', "\\|"))
+            .collect();
+        write_text(writer, &format!("| {} |\n", cells.join(" | ")))
+    }
+
+    fn finish(&mut self, _writer: &mut dyn Write) -> Result<(), CsvError> {
+        Ok(())
+    }
+}
+
+/// HTML
+/// A `<table>` element with the header in `<thead>` and the rows in `<tbody>`.
+#[derive(Default)]
+pub struct HtmlWriter {
+    columns: usize,
+}
+
+impl OutputWriter for HtmlWriter {
+    fn write_header(&mut self, writer: &mut dyn Write, header: &[String]) -> Result<(), CsvError> {
+        self.columns = header.len();
+        let cells: String = header.iter().map(|name| format!("<th>{}</th>", get_html_text(name))).collect();
+        write_text(writer, &format!("<table>\n  <thead>\n    <tr>{}</tr>\n  </thead>\n  <tbody>\n", cells))
+    }
+
+    fn write_row(&mut self, writer: &mut dyn Write, row: &[String]) -> Result<(), CsvError> {
+        let cells: String = (0..self.columns.max(row.len()))
+            .map(|x| format!("<td>{}</td>", get_html_text(get_display_text(row.get(x)))))
+            .collect();
+        write_text(writer, &format!("    <tr>{}</tr>\n", cells))
+    }
+
+    fn finish(&mut self, writer: &mut dyn Write) -> Result<(), CsvError> {
+        write_text(writer, "  </tbody>\n</table>\n")
+    }
+}
+
+/// TABLE
+/// An aligned plain text table for terminals (numbers are aligned to the right).
+/// The rows are kept until `finish` because the widths of the columns depend on every row.
+#[derive(Default)]
+pub struct TableWriter {
+    rows: Vec<Vec<String>>,
+}
+
+impl OutputWriter for TableWriter {
+    fn write_header(&mut self, _writer: &mut dyn Write, header: &[String]) -> Result<(), CsvError> {
+        self.rows.push(header.to_vec());
+        Ok(())
+    }
+
+    fn write_row(&mut self, _writer: &mut dyn Write, row: &[String]) -> Result<(), CsvError> {
+        self.rows.push(row.iter().map(|item| get_display_text(Some(item)).to_string()).collect());
+        Ok(())
+    }
+
+    fn finish(&mut self, writer: &mut dyn Write) -> Result<(), CsvError> {
+        let columns = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|x| self.rows.iter().filter_map(|row| row.get(x)).map(|item| item.chars().count()).max().unwrap_or(0))
+            .collect();
+
+        let mut lines: Vec<String> = self.rows.iter()
+            .map(|row| {
+                let cells: Vec<String> = widths.iter().enumerate()
+                    .map(|(x, width)| match row.get(x) {
+                        Some(item) if item.parse::<f64>().is_ok() => format!("{:>width$}", item),
+                        Some(item) => format!("{:<width$}", item),
+                        None       => " ".repeat(*width),
+                    })
+                    .collect();
+                cells.join(" | ").trim_end().to_string()
+            })
+            .collect();
+
+        // (A line separates the header from the body)
+        if !lines.is_empty() {
+            let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+            lines.insert(1, separator.join("-+-"));
+        }
+
+        self.rows.clear();
+        write_text(writer, &lines.iter().map(|line| format!("{}\n", line)).collect::<String>())
+    }
+}
+
+/// Returns the text of a cell for the formats meant to be read (empty cells are empty instead of "#[NULL]").
+fn get_display_text(item: Option<&String>) -> &str {
+    match item.map(String::as_str) {
+        None | Some("#[NULL]") => "",
+        Some(item) => item,
+    }
+}
+
+/// Returns a text with the HTML special characters escaped.
+fn get_html_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Writes a text to a writer or returns a CsvError::FileError().
+fn write_text(writer: &mut dyn Write, text: &str) -> Result<(), CsvError> {
+    match writer.write_all(text.as_bytes()) {
+        Ok(_)  => Ok(()),
+        Err(_) => Err(CsvError::FileError("Could not write to output file...".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes rows with an output writer and returns the text.
+    fn write_rows(output_writer: &mut dyn OutputWriter, header: &[&str], rows: &[&[&str]]) -> String {
+        let mut output: Vec<u8> = Vec::new();
+        let to_strings = |row: &[&str]| row.iter().map(|item| item.to_string()).collect::<Vec<String>>();
+
+        output_writer.write_header(&mut output, &to_strings(header)).unwrap();
+        rows.iter().for_each(|row| output_writer.write_row(&mut output, &to_strings(row)).unwrap());
+        output_writer.finish(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn json_numbers_keep_their_text() {
+        let json = write_rows(&mut JsonWriter::default(), &["A", "B", "C", "D", "E", "F"], &[&["35", "-1.5", "007", "+5", "1e3", "#[NULL]"]]);
+        assert_eq!(json, "[\n  {\"A\":35,\"B\":-1.5,\"C\":\"007\",\"D\":\"+5\",\"E\":\"1e3\",\"F\":null}\n]\n");
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        let json = write_rows(&mut JsonWriter::default(), &["A", "B"], &[&["say \"hi\"", "NaN"], &["x"]]);
+        assert_eq!(json, "[\n  {\"A\":\"say \\\"hi\\\"\",\"B\":\"NaN\"},\n  {\"A\":\"x\",\"B\":null}\n]\n");
+    }
+
+    #[test]
+    fn markdown_cells_are_escaped() {
+        let markdown = write_rows(&mut MarkdownWriter::default(), &["A", "B"], &[&["a|b", "#[NULL]"]]);
+        assert_eq!(markdown, "| A | B |\n| --- | --- |\n| a\\|b |  |\n");
+    }
+
+    #[test]
+    fn html_cells_are_escaped() {
+        let html = write_rows(&mut HtmlWriter::default(), &["A", "B"], &[&["<b>&", "#[NULL]"]]);
+        assert!(html.contains("<tr><th>A</th><th>B</th></tr>"), "{}", html);
+        assert!(html.contains("<tr><td>&lt;b&gt;&amp;</td><td></td></tr>"), "{}", html);
+        assert!(html.ends_with("</tbody>\n</table>\n"), "{}", html);
+    }
+
+    #[test]
+    fn table_columns_are_aligned_to_every_row() {
+        let table = write_rows(&mut TableWriter::default(), &["A", "B"], &[&["1.5", "abc"], &["-200", "x"]]);
+        // (Numbers are aligned to the right, texts to the left)
+        assert_eq!(table, "A    | B\n-----+----\n 1.5 | abc\n-200 | x\n");
+    }
+
+    #[test]
+    fn formats_are_found_by_name_and_extension() {
+        assert_eq!(OutputFormat::from_name("md"), Some(OutputFormat::Markdown));
+        assert_eq!(OutputFormat::from_name("xlsx"), None);
+        assert_eq!(OutputFormat::from_path("out/report.htm"), Some(OutputFormat::Html));
+        assert_eq!(OutputFormat::from_path("report"), None);
+        [OutputFormat::Csv, OutputFormat::Json, OutputFormat::Markdown, OutputFormat::Html, OutputFormat::Table].iter()
+            .for_each(|format| assert_eq!(OutputFormat::from_path(&format!("out.{}", format.get_extension())), Some(*format)));
+    }
+}
@@ -6,23 +6,24 @@ use super::exprs::{
     CmpOp,
     Functions,
 };
+use super::explain::record;
 
-/// ---------------------------------------------------
-/// -------------------- FUNCTIONS --------------------
-/// ---------------------------------------------------
+// ---------------------------------------------------
+// -------------------- FUNCTIONS --------------------
+// ---------------------------------------------------
 
 /// ---------------------------------------------------
 /// --------------------   Caller  --------------------
 /// ---------------------------------------------------
-/// This function receives a Func and arguments and calls the proper func_<name>.
-pub fn func_caller(csv: &CSV, func: &Token, args: &Vec<Token>) -> Result<String, CsvError> {
+/// This function receives a Func and arguments and calls the proper `func_<name>`.
+pub fn func_caller(csv: &CSV, func: &Token, args: &[Token]) -> Result<String, CsvError> {
     // Check whether func is truly a function token:
     if let Token::Func(f) = func {
         match f {
-            Functions::Sum  => Ok(func_sum(&csv, &args)?.to_string()),
-            Functions::Avg  => Ok(func_avg(&csv, &args)?.to_string()),
-            Functions::Calc => Ok(func_calc(&csv, &args)?.to_string()),
-            Functions::If   => Ok(func_if(&csv, &args)?),
+            Functions::Sum  => Ok(func_sum(csv, args)?.to_string()),
+            Functions::Avg  => Ok(func_avg(csv, args)?.to_string()),
+            Functions::Calc => Ok(func_calc(csv, args)?.to_string()),
+            Functions::If   => Ok(func_if(csv, args)?),
         }
     }
     else {
@@ -33,9 +34,7 @@ pub fn func_caller(csv: &CSV, func: &Token, args: &Vec<Token>) -> Result<String,
 /// ---------------------------------------------------
 /// --------------------     IF    --------------------
 /// ---------------------------------------------------
-fn func_if(csv: &CSV, args: &Vec<Token>) -> Result<String, CsvError> {
-    // println!("[IF ARGS] {:?}", args);
-
+fn func_if(csv: &CSV, args: &[Token]) -> Result<String, CsvError> {
     // Split into 3 parts:
     let mut i = 0;
 
@@ -53,8 +52,6 @@ fn func_if(csv: &CSV, args: &Vec<Token>) -> Result<String, CsvError> {
         }
         acc      
     });
-    
-    // println!("[SPLIT IF] {:?}", split_if);
 
     if split_if.len() != 3 {
         return Err(CsvError::ArgError);
@@ -62,15 +59,14 @@ fn func_if(csv: &CSV, args: &Vec<Token>) -> Result<String, CsvError> {
     
     // Checking the condition:
     let cond_args = &split_if[0];
-    let cond_val = condition_eval(&csv, &cond_args)?;
-    let output;
-
-    if cond_val {
-        output = &split_if[1];
+    let cond_val = condition_eval(csv, cond_args)?;
+    record(|| format!("IF takes the {} branch", if cond_val { "THEN" } else { "ELSE" }));
+    let output = if cond_val {
+        &split_if[1]
     }
     else {
-        output = &split_if[2];
-    }
+        &split_if[2]
+    };
 
     if output.is_empty() {
         return Err(CsvError::ArgError);
@@ -79,15 +75,13 @@ fn func_if(csv: &CSV, args: &Vec<Token>) -> Result<String, CsvError> {
     match &output[0] {
         Token::Cell(val) => Ok(val.to_string()), // What if the cell contains a cell pointer?
         Token::Number(n) => Ok(n.to_string()),
-        Token::Func(_)   => func_caller(&csv, &output[0], &output[1..output.len()].to_vec()),
+        Token::Func(_)   => func_caller(csv, &output[0], &output[1..output.len()]),
         _ => Err(CsvError::ExprError("Wrong argument type...".to_string())),
     }
 }
 
 // Evaluates whether a condition is true or false:
-fn condition_eval(csv: &CSV, cond_args: &Vec<Token>) -> Result<bool, CsvError> {
-    // println!("[COND ARGS] {:?}", cond_args);
-
+fn condition_eval(csv: &CSV, cond_args: &[Token]) -> Result<bool, CsvError> {
     if cond_args.len() != 3 {
         return Err(CsvError::ArgError);
     }
@@ -97,18 +91,19 @@ fn condition_eval(csv: &CSV, cond_args: &Vec<Token>) -> Result<bool, CsvError> {
     let right = &cond_args[2];
     
     // Extracting the values from left and right:
-    let (l_val, r_val) = get_cmp_values(&csv, &left, &right)?;
-    // println!("[LEFT] {:?}, [RIGHT] {:?}", l_val, r_val);
+    let (l_val, r_val) = get_cmp_values(csv, left, right)?;
 
     // Comparing left and right:
     if let Token::CmpOperator(cmp) = op {
-        match cmp {
-            CmpOp::Eq => Ok(CmpOp::eq(l_val, r_val)),
-            CmpOp::Gt => Ok(CmpOp::gt(l_val, r_val)),
-            CmpOp::Lt => Ok(CmpOp::lt(l_val, r_val)),
-            CmpOp::Ge => Ok(CmpOp::ge(l_val, r_val)),
-            CmpOp::Le => Ok(CmpOp::le(l_val, r_val)),
-        } 
+        let cond_val = match cmp {
+            CmpOp::Eq => CmpOp::eq(l_val, r_val),
+            CmpOp::Gt => CmpOp::gt(l_val, r_val),
+            CmpOp::Lt => CmpOp::lt(l_val, r_val),
+            CmpOp::Ge => CmpOp::ge(l_val, r_val),
+            CmpOp::Le => CmpOp::le(l_val, r_val),
+        };
+        record(|| format!("condition {} {} {} is {}", l_val, cmp.get_symbol(), r_val, cond_val));
+        Ok(cond_val)
     }
     else {
         Err(CsvError::ExprError("Expected a comparison operator...".to_string()))
@@ -121,35 +116,35 @@ fn get_cmp_values(csv: &CSV, left: &Token, right: &Token) -> Result<(f64, f64),
     match (left, right) {
         (Token::Number(n), Token::Number(k)) => Ok((*n, *k)),
         (Token::Cell(c1), Token::Cell(c2))   => {
-            let n = match csv.get_cell_value(c1)?.parse::<f64>() {
-                Ok(n)  => n,
-                Err(_) => return Err(CsvError::ExprError("Uncomparable ypes...".to_string())),
+            let n = match csv.get_cell_number(c1)? {
+                Some(n) => n,
+                None    => return Err(CsvError::ExprError("Uncomparable ypes...".to_string())),
             };
 
-            let k = match csv.get_cell_value(c2)?.parse::<f64>() {
-                Ok(k)  => k,
-                Err(_) => return Err(CsvError::ExprError("Uncomparable ypes...".to_string())),
+            let k = match csv.get_cell_number(c2)? {
+                Some(k) => k,
+                None    => return Err(CsvError::ExprError("Uncomparable ypes...".to_string())),
             };
 
-            Ok((n.clone(), k.clone()))
+            Ok((n, k))
         }, 
         (Token::Number(n), Token::Cell(cptr)) => {
-            let k = match csv.get_cell_value(cptr)?.parse::<f64>() {
-                Ok(k)  => k,
-                Err(_) => return Err(CsvError::ExprError("Uncomparable ypes...".to_string())),
+            let k = match csv.get_cell_number(cptr)? {
+                Some(k) => k,
+                None    => return Err(CsvError::ExprError("Uncomparable ypes...".to_string())),
             };
 
-            Ok((*n, k.clone()))
+            Ok((*n, k))
         },
         (Token::Cell(cptr), Token::Number(n)) => {
-            let k = match csv.get_cell_value(cptr)?.parse::<f64>() {
-                Ok(k)  => k,
-                Err(_) => return Err(CsvError::ExprError("Uncomparable ypes...".to_string())),
+            let k = match csv.get_cell_number(cptr)? {
+                Some(k) => k,
+                None    => return Err(CsvError::ExprError("Uncomparable ypes...".to_string())),
             };
 
-            Ok((k.clone(), *n))
+            Ok((k, *n))
         },
-        _ => return Err(CsvError::ExprError("Uncomparable types...".to_string())),
+        _ => Err(CsvError::ExprError("Uncomparable types...".to_string())),
     }
 }
 
@@ -158,9 +153,16 @@ fn get_cmp_values(csv: &CSV, left: &Token, right: &Token) -> Result<(f64, f64),
 /// ---------------------------------------------------
 /// Evaluates a mathematical expression;
 /// It will turn the received arguments (which should be numbers, cells or binary operators) into postfix form;
-fn func_calc(csv: &CSV, args: &Vec<Token>) -> Result<f64, CsvError> {
-    let postfix_args = infix_to_postfix(&csv, &args)?;
-    //println!("[POSTFIX AGRS] {:?}", postfix_args);
+fn func_calc(csv: &CSV, args: &[Token]) -> Result<f64, CsvError> {
+    let postfix_args = infix_to_postfix(csv, args)?;
+    record(|| {
+        let words: Vec<String> = postfix_args.iter().map(|token| match token {
+            Token::Number(n)    => n.to_string(),
+            Token::Operator(op) => op.get_symbol().to_string(),
+            _ => "?".to_string(),
+        }).collect();
+        format!("CALC in postfix form: {}", words.join(" "))
+    });
 
     let mut stack: Vec<f64> = Vec::new();
         
@@ -168,10 +170,10 @@ fn func_calc(csv: &CSV, args: &Vec<Token>) -> Result<f64, CsvError> {
         match token {
             Token::Number(n) => {
                 stack.push(*n);
+                record(|| format!("push {}: stack {}", n, get_stack_text(&stack)));
                 Ok(())
             },
             Token::Operator(op) => {
-                //println!("[STACK] {:?} [LEN] {}", stack, stack.len());
                 let val1 = match stack.pop() {
                     Some(v) => v,
                     None    => return Err(CsvError::ExprError("Incorrect math expression...".to_string())),
@@ -189,6 +191,7 @@ fn func_calc(csv: &CSV, args: &Vec<Token>) -> Result<f64, CsvError> {
                     BinaryOp::Mult  => stack.push(val2 * val1),
                     BinaryOp::Div   => stack.push(val2 / val1),
                 }
+                record(|| format!("{} {} {} = {}: stack {}", val2, op.get_symbol(), val1, stack.last().unwrap(), get_stack_text(&stack)));
 
                 Ok(())
             }
@@ -207,26 +210,32 @@ fn func_calc(csv: &CSV, args: &Vec<Token>) -> Result<f64, CsvError> {
     Ok(*stack.last().unwrap())
 }
 
+/// Returns the values of the CALC stack as text ("[3, 4]").
+fn get_stack_text(stack: &[f64]) -> String {
+    format!("[{}]", stack.iter().map(f64::to_string).collect::<Vec<_>>().join(", "))
+}
+
 /// Parses arguments into postfix form for `func_calc`:
 /// Iterates over the received arguments and forms a postfix expression from them;
 /// This way I don't have to deal with precedence checking;
-fn infix_to_postfix(csv: &CSV, args: &Vec<Token>) -> Result<Vec<Token>, CsvError> {
-    //println!("[FROM POSTIX PARSER] {:?}", args);
+fn infix_to_postfix(csv: &CSV, args: &[Token]) -> Result<Vec<Token>, CsvError> {
 
     let mut postfix: Vec<Token> = Vec::new();
     let mut stack: Vec<BinaryOp> = Vec::new();    
     
-    let _ = args.iter().try_for_each(|token| {
+    args.iter().try_for_each(|token| {
         match token {
             Token::Number(n) => { 
                 postfix.push(Token::Number(*n));
                 Ok(())
             },
             Token::Cell(cell_ptr) => {
-                let value = csv.get_cell_value(cell_ptr)?;
-                match value.parse::<f64>() {
-                    Ok(n)  => postfix.push(Token::Number(n)),
-                    Err(_) => return Err(CsvError::ExprError("NaN".to_string()))
+                match csv.get_cell_number(cell_ptr)? {
+                    Some(n) => postfix.push(Token::Number(n)),
+                    None    => {
+                        record(|| format!("{} doesn't hold a number", cell_ptr));
+                        return Err(CsvError::ExprError("NaN".to_string()));
+                    },
                 }
                 Ok(())
             },
@@ -234,7 +243,7 @@ fn infix_to_postfix(csv: &CSV, args: &Vec<Token>) -> Result<Vec<Token>, CsvError
                 while !stack.is_empty() && !op.check_precendece(stack.last().unwrap()) {
                     postfix.push(Token::Operator(stack.pop().unwrap()));
                 }
-                stack.push(op.clone());
+                stack.push(*op);
 
                 Ok(()) 
             }
@@ -244,9 +253,8 @@ fn infix_to_postfix(csv: &CSV, args: &Vec<Token>) -> Result<Vec<Token>, CsvError
     
     // Finishing up the stack:
     while !stack.is_empty() {
-        match stack.pop() {
-            Some(op) => postfix.push(Token::Operator(op.clone())),
-            None     => {},
+        if let Some(op) = stack.pop() {
+            postfix.push(Token::Operator(op));
         }
     }
 
@@ -254,43 +262,55 @@ fn infix_to_postfix(csv: &CSV, args: &Vec<Token>) -> Result<Vec<Token>, CsvError
 }
 
 /// ---------------------------------------------------
-/// --------------------    AVG    --------------------
+/// --------------------    SUM    --------------------
 /// ---------------------------------------------------
-fn func_sum(csv: &CSV, args: &Vec<Token>) -> Result<f64, CsvError> {
-    // Incorrect argument size:
-    if args.len() != 2 {
-        return Err(CsvError::ArgError);
-    }
-
+fn func_sum(csv: &CSV, args: &[Token]) -> Result<f64, CsvError> {
     // Extracting argument values:
-    let arg1 = args[0].get_cell();
-    let arg2 = args[1].get_cell();
+    let (arg1, arg2) = get_range_args(args)?;
+
+    // Numeric columns are summed straight from their buffer:
+    if let Some(numbers) = csv.get_range_numbers(&arg1, &arg2) {
+        let sum = numbers.iter().fold(0.0, |acc, n| acc + n);
+        record(|| format!("SUM of {} values from {} to {} = {}", numbers.len(), arg1, arg2, sum));
+        return Ok(sum);
+    }
 
     // Getting range values:
     let range_values = csv.get_range_values(&arg1, &arg2)?;
     
-    Ok(range_values.iter().try_fold(0.0, |acc, item| {
+    let sum = range_values.iter().try_fold(0.0, |acc, item| {
         match item.parse::<f64>() {
             Ok(val) => Ok(acc + val),
             Err(_) => Err(CsvError::ExprError("NaN".to_string())), 
         }
-    })?)
+    })?;
+    record(|| format!("SUM of {} values from {} to {} = {}", range_values.len(), arg1, arg2, sum));
+    Ok(sum)
 }
 
 /// ---------------------------------------------------
 /// --------------------    AVG    --------------------
 /// ---------------------------------------------------
-fn func_avg(csv: &CSV, args: &Vec<Token>) -> Result<f64, CsvError> {
-    // Incorrect argument size:
-    if args.len() != 2 {
-        return Err(CsvError::ArgError);
-    }
+fn func_avg(csv: &CSV, args: &[Token]) -> Result<f64, CsvError> {
+    // Extracting argument values:
+    let (arg1, arg2) = get_range_args(args)?;
     
     // Calculating range length:
-    let range_len = csv.get_range_len(&args[0].get_cell(), &args[1].get_cell())?;
+    let range_len = csv.get_range_len(&arg1, &arg2)?;
 
     // To get the sum I reuse `func_sum`:
     let sum: f64 = func_sum(csv, args)?;
+    record(|| format!("AVG = {} / {} = {}", sum, range_len, sum / range_len as f64));
 
     Ok(sum / range_len as f64)
 }
+
+/// Extracts the start and the end of a range argument.
+/// A range is either given as 2 cell pointers ("A1 A5") or as 1 word ("A1:A5", "rates.csv!A1:A5").
+fn get_range_args(args: &[Token]) -> Result<(String, String), CsvError> {
+    match args {
+        [Token::Range(start, end)] => Ok((start.clone(), end.clone())),
+        [start, end]               => Ok((start.get_cell(), end.get_cell())),
+        _ => Err(CsvError::ArgError),
+    }
+}
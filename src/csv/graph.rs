@@ -0,0 +1,310 @@
+use std::collections::{HashMap, HashSet};
+use std::panic;
+use std::thread;
+use crate::csv::{CSV, RangeType};
+use crate::error::CsvError;
+use super::exprs::{Token, Functions};
+use super::refs::CellRef;
+
+/// Coordinates of a cell (x, y).
+pub type Cell = (usize, usize);
+
+/// DEPENDENCY GRAPH
+/// Tracks which cells every expression reads (precedents) and which expressions read a cell (dependents).
+/// The references are taken from the tokens of the expressions, so only cells of the same sheet are tracked.
+#[derive(Debug, Default)]
+pub struct DepGraph {
+    precedents: HashMap<Cell, Vec<Cell>>,
+    dependents: HashMap<Cell, Vec<Cell>>,
+}
+
+impl DepGraph {
+    /// Builds the dependency graph of the given cells of a CSV object.
+    /// The references of the expressions are collected on the given amount of threads.
+    pub fn build(csv: &CSV, cells: &[Cell], threads: usize) -> Self {
+        let collect = |cells: &[Cell]| -> Vec<(Cell, Vec<Cell>)> {
+            cells.iter().map(|&(x, y)| ((x, y), get_references(&csv.get_cell_source(x, y), csv))).collect()
+        };
+
+        let references: Vec<(Cell, Vec<Cell>)> = if threads == 1 || cells.is_empty() {
+            collect(cells)
+        }
+        else {
+            thread::scope(|scope| {
+                let handles: Vec<_> = cells.chunks(cells.len().div_ceil(threads))
+                    .map(|chunk| scope.spawn(move || collect(chunk)))
+                    .collect();
+
+                handles.into_iter()
+                    .flat_map(|handle| handle.join().unwrap_or_else(|err| panic::resume_unwind(err)))
+                    .collect()
+            })
+        };
+
+        let mut graph = Self::default();
+        references.into_iter().for_each(|(cell, precedents)| graph.set_precedents(cell, precedents));
+        graph
+    }
+
+    /// Replaces the precedents of a cell.
+    pub fn set_precedents(&mut self, cell: Cell, precedents: Vec<Cell>) {
+        // Removing the old edges:
+        if let Some(old) = self.precedents.remove(&cell) {
+            old.iter().for_each(|precedent| {
+                if let Some(dependents) = self.dependents.get_mut(precedent) {
+                    dependents.retain(|dependent| *dependent != cell);
+                }
+            });
+        }
+
+        if precedents.is_empty() {
+            return;
+        }
+
+        precedents.iter().for_each(|precedent| {
+            self.dependents.entry(*precedent).or_default().push(cell);
+        });
+        self.precedents.insert(cell, precedents);
+    }
+
+    /// Returns the cells the given cell reads directly.
+    pub fn get_precedents(&self, cell: Cell) -> &[Cell] {
+        self.precedents.get(&cell).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Returns the cells that read the given cell directly.
+    pub fn get_dependents(&self, cell: Cell) -> &[Cell] {
+        self.dependents.get(&cell).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Returns every cell the given cell reads directly or indirectly (without the cell itself, unless it's in a cycle).
+    pub fn get_all_precedents(&self, cell: Cell) -> HashSet<Cell> {
+        let mut reached: HashSet<Cell> = HashSet::new();
+        let mut stack: Vec<Cell> = vec![cell];
+
+        while let Some(cell) = stack.pop() {
+            self.get_precedents(cell).iter().for_each(|precedent| {
+                if reached.insert(*precedent) {
+                    stack.push(*precedent);
+                }
+            });
+        }
+
+        reached
+    }
+
+    /// Returns every edge of the graph as (precedent, dependent).
+    pub fn get_edges(&self) -> Vec<(Cell, Cell)> {
+        self.precedents.iter()
+            .flat_map(|(cell, precedents)| precedents.iter().map(move |precedent| (*precedent, *cell)))
+            .collect()
+    }
+
+    /// Returns the given cells and every cell that (directly or indirectly) reads them.
+    pub fn get_affected(&self, cells: &[Cell]) -> HashSet<Cell> {
+        let mut affected: HashSet<Cell> = cells.iter().copied().collect();
+        let mut stack: Vec<Cell> = cells.to_vec();
+
+        while let Some(cell) = stack.pop() {
+            if let Some(dependents) = self.dependents.get(&cell) {
+                dependents.iter().for_each(|dependent| {
+                    if affected.insert(*dependent) {
+                        stack.push(*dependent);
+                    }
+                });
+            }
+        }
+
+        affected
+    }
+
+    /// Splits the given cells into levels: every cell only reads cells of earlier levels,
+    /// so the cells of 1 level are independent of each other and can be evaluated in parallel.
+    /// Cells that are part of a circular reference are put into the last level.
+    pub fn levels(&self, cells: &HashSet<Cell>) -> Vec<Vec<Cell>> {
+        // Counting the precedents of every cell inside the set:
+        let mut counts: HashMap<Cell, usize> = cells.iter()
+            .map(|cell| (*cell, self.get_precedents(*cell).iter().filter(|precedent| cells.contains(precedent)).count()))
+            .collect();
+
+        let mut levels: Vec<Vec<Cell>> = Vec::new();
+        let mut level: Vec<Cell> = counts.iter().filter(|(_, count)| **count == 0).map(|(cell, _)| *cell).collect();
+
+        while !level.is_empty() {
+            let mut next: Vec<Cell> = Vec::new();
+
+            level.iter().for_each(|cell| {
+                counts.remove(cell);
+
+                if let Some(dependents) = self.dependents.get(cell) {
+                    dependents.iter().for_each(|dependent| {
+                        if let Some(count) = counts.get_mut(dependent) {
+                            *count -= 1;
+                            if *count == 0 {
+                                next.push(*dependent);
+                            }
+                        }
+                    });
+                }
+            });
+
+            level.sort_unstable_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+            levels.push(level);
+            level = next;
+        }
+
+        // Leftovers are in a cycle:
+        if !counts.is_empty() {
+            let mut cyclic: Vec<Cell> = counts.into_keys().collect();
+            cyclic.sort_unstable_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+            levels.push(cyclic);
+        }
+
+        levels
+    }
+}
+
+/// RECALCULATION STATE
+/// The dependency graph and the cached values of every expression.
+/// Present on a CSV object after `CSV::recalculate` was called.
+/// `threads` is the amount of threads the cells of 1 level are evaluated on.
+#[derive(Debug)]
+pub struct Recalc {
+    pub graph: DepGraph,
+    pub values: HashMap<Cell, Result<String, CsvError>>,
+    pub threads: usize,
+}
+
+/// Returns the cells of the same sheet an item reads.
+/// Ranges of SUM and AVG are expanded to every cell inside them; cells of linked files and invalid cell pointers are skipped.
+/// A cell pointer right after THEN or ELSE isn't a reference either: IF returns it as text.
+pub fn get_references(item: &str, csv: &CSV) -> Vec<Cell> {
+    if !item.starts_with('=') {
+        return Vec::new();
+    }
+
+    let tokens = Token::tokenize(&item[1..item.len()]);
+    let mut references: Vec<Cell> = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        match (&tokens[i], tokens.get(i + 1), tokens.get(i + 2)) {
+            // Range given as 2 cell pointers:
+            (Token::Func(Functions::Sum | Functions::Avg), Some(Token::Cell(start)), Some(Token::Cell(end))) => {
+                references.extend(get_range_cells(csv, start, end));
+                i += 3;
+                continue;
+            },
+            // Branch returned as text:
+            (Token::Then | Token::Else, Some(Token::Cell(_)), _) => {
+                i += 2;
+                continue;
+            },
+            (Token::Range(start, end), _, _) => references.extend(get_range_cells(csv, start, end)),
+            (Token::Cell(cell_pointer), _, _) => references.extend(get_cell(csv, cell_pointer)),
+            _ => {},
+        }
+        i += 1;
+    }
+
+    references.sort_unstable();
+    references.dedup();
+    references
+}
+
+/// Returns the coordinates of a cell pointer of the same sheet.
+fn get_cell(csv: &CSV, cell_pointer: &str) -> Option<Cell> {
+    // (Words that aren't cell pointers, like the texts of IF branches, aren't references)
+    if cell_pointer.contains('!') || CellRef::parse(cell_pointer).is_none() {
+        return None;
+    }
+
+    // (Rows below the body are kept so cells added later are tracked too)
+    Some((csv.get_column_cor(cell_pointer).ok()?, CSV::parse_row_cor(cell_pointer).ok()?))
+}
+
+/// Returns the coordinates of every cell inside a range of the same sheet.
+fn get_range_cells(csv: &CSV, start: &str, end: &str) -> Vec<Cell> {
+    let (Some((x_start, y_start)), Some((x_end, y_end))) = (get_cell(csv, start), get_cell(csv, end)) else {
+        return Vec::new();
+    };
+
+    match RangeType::get_range_type(x_start, x_end, y_start, y_end) {
+        Some(RangeType::Row) => (x_start..=x_end).map(|x| (x, y_start)).collect(),
+        Some(RangeType::Col) => (y_start..=y_end).map(|y| (x_start, y)).collect(),
+        None => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_pointers_are_not_references() {
+        let csv = CSV::from_string("test.csv", "A,B\n1,= CALC é1 + 1\n2,= IF A1 > 0 THEN yes ELSE no\n").unwrap();
+        assert!(get_references("= CALC é1 + 1", &csv).is_empty());
+        assert!(get_references("= CALC  + 1", &csv).is_empty());
+        assert_eq!(get_references("= IF A1 > 0 THEN yes ELSE no", &csv), vec![(0, 0)]);
+    }
+
+    #[test]
+    fn branches_returned_as_text_are_not_references() {
+        let mut csv = CSV::from_string("test.csv", "A,B,C\n1,2,= IF A1 > 0 THEN B1 ELSE A2\n3,4,= IF A2 > 0 THEN SUM B1 B2 ELSE 0\n").unwrap();
+        assert_eq!(get_references("= IF A1 > 0 THEN B1 ELSE A2", &csv), vec![(0, 0)]);
+        assert_eq!(get_references("= IF A2 > 0 THEN SUM B1 B2 ELSE 0", &csv), vec![(0, 1), (1, 0), (1, 1)]);
+
+        csv.recalculate();
+        assert_eq!(csv.get_cell_value("C1").unwrap(), "B1");
+        assert_eq!(csv.get_precedents("C1", false).unwrap(), vec!["A1"]);
+        let dot = csv.to_dot();
+        assert!(dot.contains("\"A1\" -> \"C1\";") && !dot.contains("\"B1\" -> \"C1\";"));
+    }
+
+    #[test]
+    fn recalculate_reports_invalid_pointers_as_cell_errors() {
+        let mut csv = CSV::from_string("test.csv", "A,B\n1,= CALC é1 + 1\n").unwrap();
+        csv.recalculate();
+
+        let errors = csv.get_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, "B1");
+        assert!(matches!(errors[0].1, CsvError::CellPError(_)));
+    }
+
+    #[test]
+    fn references_expand_ranges() {
+        let csv = CSV::from_string("test.csv", "A,B\n1,2\n3,4\n5,= SUM A1 A3\n").unwrap();
+        assert_eq!(get_references("= SUM A1 A3", &csv), vec![(0, 0), (0, 1), (0, 2)]);
+        assert_eq!(get_references("= AVG A1:B1", &csv), vec![(0, 0), (1, 0)]);
+        assert!(get_references("= SUM rates.csv!A1 rates.csv!A3", &csv).is_empty());
+    }
+
+    #[test]
+    fn levels_order_dependencies() {
+        let mut graph = DepGraph::default();
+        graph.set_precedents((1, 0), vec![(0, 0)]);
+        graph.set_precedents((2, 0), vec![(1, 0)]);
+        graph.set_precedents((3, 0), vec![(0, 0)]);
+
+        let cells: HashSet<Cell> = [(1, 0), (2, 0), (3, 0)].into_iter().collect();
+        assert_eq!(graph.levels(&cells), vec![vec![(1, 0), (3, 0)], vec![(2, 0)]]);
+        assert_eq!(graph.get_affected(&[(1, 0)]), [(1, 0), (2, 0)].into_iter().collect());
+
+        // Replacing the precedents removes the old edges:
+        graph.set_precedents((2, 0), vec![(3, 0)]);
+        assert!(graph.get_dependents((1, 0)).is_empty());
+        assert_eq!(graph.get_dependents((3, 0)), &[(2, 0)]);
+    }
+
+    #[test]
+    fn cycles_are_put_into_the_last_level() {
+        let mut graph = DepGraph::default();
+        graph.set_precedents((0, 0), vec![(1, 0)]);
+        graph.set_precedents((1, 0), vec![(0, 0)]);
+        graph.set_precedents((2, 0), vec![(3, 0)]);
+
+        let cells: HashSet<Cell> = [(0, 0), (1, 0), (2, 0)].into_iter().collect();
+        assert_eq!(graph.levels(&cells), vec![vec![(2, 0)], vec![(0, 0), (1, 0)]]);
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt;
+use std::fs;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use crate::csv::CSV;
+use crate::error::CsvError;
+
+/// LINKED SHEETS
+/// Cache of the csv files referenced by formulas (`rates.csv!B2`).
+/// The files are loaded lazily (the first time a formula reaches them) and are shared between every linked CSV object.
+/// Sheets of a workbook can also be reached by their name (`rates!B2`).
+#[derive(Default)]
+pub struct Links {
+    sheets: Mutex<HashMap<PathBuf, Arc<CSV>>>,
+    named: Mutex<HashMap<String, Arc<CSV>>>,
+}
+
+impl Links {
+    /// Returns the linked sheet of the given path, loading it if it isn't cached yet.
+    pub fn load(links: &Arc<Links>, path: &Path) -> Result<Arc<CSV>, CsvError> {
+        let key = match fs::canonicalize(path) {
+            Ok(key) => key,
+            Err(_)  => return Err(CsvError::FileError(format!("Could not find linked csv file: {}...", path.display()))),
+        };
+
+        let mut sheets = match links.sheets.lock() {
+            Ok(sheets) => sheets,
+            Err(_)     => return Err(CsvError::RunError("Linked sheet cache is poisoned...".to_string())),
+        };
+
+        if let Some(sheet) = sheets.get(&key) {
+            return Ok(Arc::clone(sheet));
+        }
+
+        let sheet = Arc::new(CSV::new_linked(path.display().to_string(), LinkHandle::Shared(Arc::downgrade(links)))?);
+        sheets.insert(key, Arc::clone(&sheet));
+        Ok(sheet)
+    }
+
+    /// Loads the sheet of the given path and makes it reachable by the given name.
+    pub fn register(links: &Arc<Links>, name: &str, path: &Path) -> Result<Arc<CSV>, CsvError> {
+        let sheet = Self::load(links, path)?;
+
+        match links.named.lock() {
+            Ok(mut named) => {
+                if named.insert(name.to_string(), Arc::clone(&sheet)).is_some() {
+                    return Err(CsvError::FileError(format!("Sheet name is used more than once: {}...", name)));
+                }
+            },
+            Err(_) => return Err(CsvError::RunError("Linked sheet cache is poisoned...".to_string())),
+        }
+
+        Ok(sheet)
+    }
+
+    /// Returns the paths of every sheet loaded so far.
+    pub fn get_paths(&self) -> Vec<PathBuf> {
+        match self.sheets.lock() {
+            Ok(sheets) => sheets.keys().cloned().collect(),
+            Err(_)     => Vec::new(),
+        }
+    }
+
+    /// Returns the sheet registered with the given name.
+    pub fn get_named(&self, name: &str) -> Option<Arc<CSV>> {
+        self.named.lock().ok()?.get(name).cloned()
+    }
+}
+
+impl fmt::Debug for Links {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Only the paths are printed (the sheets point back to the links):
+        match self.sheets.lock() {
+            Ok(sheets) => f.debug_list().entries(sheets.keys()).finish(),
+            Err(_)     => write!(f, "[POISONED]"),
+        }
+    }
+}
+
+/// Handle of a CSV object to its links.
+/// The sheet that was opened by the user owns the links,
+/// sheets loaded through a link only point back to them (so the cache doesn't keep itself alive).
+#[derive(Debug)]
+pub enum LinkHandle {
+    Owned(Arc<Links>),
+    Shared(Weak<Links>),
+}
+
+impl LinkHandle {
+    /// Returns the links or a CsvError if they were already dropped.
+    pub fn get(&self) -> Result<Arc<Links>, CsvError> {
+        match self {
+            Self::Owned(links)  => Ok(Arc::clone(links)),
+            Self::Shared(links) => match links.upgrade() {
+                Some(links) => Ok(links),
+                None        => Err(CsvError::RunError("Linked sheets are no longer available...".to_string())),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns an empty directory in the temp directory.
+    fn get_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("echcell_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn cells_of_linked_files_are_read() {
+        let dir = get_dir("links");
+        fs::write(dir.join("rates.csv"), "A,B\nusd,2\neur,= CALC B1 + 1\n").unwrap();
+        fs::write(dir.join("data.csv"), "A,B\n5,= CALC A1 * rates.csv!B2\n,= SUM rates.csv!B1 rates.csv!B2\n,= CALC other.csv!A1\n").unwrap();
+
+        let csv = CSV::new(dir.join("data.csv").display().to_string()).unwrap();
+        assert_eq!(csv.get("rates.csv!A2").unwrap(), "eur");
+        assert_eq!(csv.get_cell_value("B1").unwrap(), "15");
+        assert_eq!(csv.get_cell_value("B2").unwrap(), "5");
+        assert!(matches!(csv.get_cell_value("B3"), Err(CsvError::FileError(_))));
+        assert_eq!(csv.get_linked_files(), vec![fs::canonicalize(dir.join("rates.csv")).unwrap()]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
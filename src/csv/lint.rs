@@ -0,0 +1,326 @@
+use std::ops::Range;
+use crate::csv::CSV;
+use crate::error::CsvError;
+use super::diagnostic::Diagnostic;
+use super::exprs::{Token, Functions};
+use super::graph::{Cell, get_references};
+use super::refs::CellRef;
+
+/// Checks every expression of a sheet without writing an output file.
+/// Words that are neither a function nor a cell pointer are reported first (an unknown function would otherwise
+/// show up as a confusing cell pointer error), then every expression is evaluated and its error is reported.
+/// Every problem is located: the word of the formula that caused it is marked and a hint is added where possible.
+/// "NaN" errors are replaced by the cell that doesn't hold a number if it can be found.
+/// Returns the problems ordered by rows; an empty Vec means the sheet is fine.
+pub fn check(csv: &CSV) -> Vec<Diagnostic> {
+    csv.get_cells().into_iter()
+        .filter_map(|(x, y)| {
+            let formula = csv.get_cell_source(x, y);
+            if !formula.starts_with('=') {
+                return None;
+            }
+
+            let words = get_words(&formula);
+            let (error, span, help) = match check_words(&words) {
+                Some(problem) => problem,
+                None => {
+                    let error = csv.get_evaluated_value(x, y).err()?;
+                    locate(csv, &words, error)
+                },
+            };
+
+            Some(Diagnostic { file: csv.file.clone(), cell: csv.get_cell_pointer(x, y), formula, span, error, help })
+        })
+        .collect()
+}
+
+impl CSV {
+    /// Adds the location to the error of a failing cell: the error is returned as a CsvError::CellError()
+    /// that holds the cell pointer, the formula and the word of the formula that caused it (see `check`).
+    /// Errors of cells without an expression (or of pointers that aren't cells of this sheet) are returned as they are.
+    pub fn locate_error(&self, cell_pointer: &str, error: CsvError) -> CsvError {
+        let (Ok(x), Ok(y)) = (self.get_column_cor(cell_pointer), self.get_row_cor(cell_pointer)) else {
+            return error;
+        };
+
+        let formula = self.get_cell_source(x, y);
+        if !formula.starts_with('=') || cell_pointer.contains('!') {
+            return error;
+        }
+
+        let (error, span, help) = locate(self, &get_words(&formula), error);
+        CsvError::CellError(Box::new(Diagnostic { file: self.file.clone(), cell: self.get_cell_pointer(x, y), formula, span, error, help }))
+    }
+}
+
+/// A word of a formula with its byte range.
+type Word<'a> = (Range<usize>, &'a str);
+
+/// Splits a formula into its words (the same way the tokenizer does) and keeps the byte range of each one.
+/// The leading '=' is skipped.
+fn get_words(formula: &str) -> Vec<Word<'_>> {
+    let mut words = Vec::new();
+    let mut start: Option<usize> = None;
+
+    for (i, c) in formula.char_indices().skip(1).chain([(formula.len(), ' ')]) {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                words.push((s..i, &formula[s..i]));
+                start = None;
+            },
+            (false, None) => start = Some(i),
+            _ => {},
+        }
+    }
+
+    words
+}
+
+/// Returns the token of a word.
+fn get_token(word: &str) -> Option<Token> {
+    Token::tokenize(word).pop()
+}
+
+/// Returns the problem of the first word that is neither a known word nor a cell pointer.
+/// The first word of an IF branch is its result (`THEN yes ELSE no`), so it isn't checked.
+fn check_words(words: &[Word]) -> Option<(CsvError, Option<Range<usize>>, Option<String>)> {
+    words.iter().enumerate().find_map(|(i, (span, word))| {
+        let branch = i > 0 && matches!(get_token(words[i - 1].1), Some(Token::Then | Token::Else));
+        let pointers = match get_token(word)? {
+            _ if branch => return None,
+            Token::Cell(pointer)     => vec![pointer],
+            Token::Range(start, end) => vec![start, end],
+            _ => return None,
+        };
+
+        pointers.into_iter().find_map(|pointer| {
+            // (The pointers of linked files are checked without their file prefix)
+            let local = pointer.split_once('!').map_or(pointer.as_str(), |(_, local)| local);
+            if CellRef::parse(local).is_some() {
+                None
+            }
+            else if local.len() > 1 && local.chars().all(|c| c.is_ascii_uppercase()) {
+                let help = "the functions are SUM, AVG, CALC and IF".to_string();
+                Some((CsvError::ExprError(format!("Unknown function {}...", local)), Some(span.clone()), Some(help)))
+            }
+            else {
+                let help = "a cell pointer is a column name followed by a row number (A1, $B$2, rates.csv!B2)".to_string();
+                Some((CsvError::CellPError(format!("Invalid cell pointer {}...", pointer)), Some(span.clone()), Some(help)))
+            }
+        })
+    })
+}
+
+/// Finds the word of a formula that caused an evaluation error and a hint for it.
+fn locate(csv: &CSV, words: &[Word], error: CsvError) -> (CsvError, Option<Range<usize>>, Option<String>) {
+    // Errors of the cells the formula reads are passed on:
+    let same_error = |value: &Result<String, CsvError>| value.as_ref().is_err_and(|err| err.to_string() == error.to_string());
+    if let Some((cell, _)) = find_cell(csv, words, same_error) {
+        let help = format!("the error comes from {}", csv.get_cell_pointer(cell.0, cell.1));
+        return (error, find_word(csv, words, cell), Some(help));
+    }
+
+    let (span, help) = match &error {
+        CsvError::CellPError(msg) if msg.starts_with("Column index out of bounds") => {
+            let span = find_pointer(words, |pointer| csv.get_column_cor(pointer).is_err());
+            let help = span.as_ref().and_then(|(_, pointer)| CellRef::parse(pointer)).map(|cell_ref| format!(
+                "column `{}` does not exist; header has {}", cell_ref.col, describe_header(&csv.header),
+            ));
+            (span.map(|(span, _)| span), help)
+        },
+        CsvError::CellPError(msg) if msg.starts_with("Row index out of bounds") => {
+            let span = find_pointer(words, |pointer| csv.get_row_cor(pointer).is_err());
+            let help = span.as_ref().and_then(|(_, pointer)| CellRef::parse(pointer)).map(|cell_ref| format!(
+                "row {} does not exist; the sheet has {} rows", cell_ref.row, csv.get_row_count(),
+            ));
+            (span.map(|(span, _)| span), help)
+        },
+        CsvError::RangeError(_) => {
+            let span = words.iter().find(|(_, word)| matches!(get_token(word), Some(Token::Range(..)))).map(|(span, _)| span.clone());
+            (span, Some("a range has to stay inside 1 column or 1 row (A1:A5, A1:D1)".to_string()))
+        },
+        CsvError::ArgError => {
+            let function = words.iter().find(|(_, word)| matches!(get_token(word), Some(Token::Func(_))));
+            let help = function.and_then(|(_, word)| match get_token(word) {
+                Some(Token::Func(Functions::Sum | Functions::Avg)) => Some(format!("{} expects a range: `{} A1 A5` or `{} A1:A5`", word, word, word)),
+                Some(Token::Func(Functions::If)) => Some("IF expects `IF <value> <comparison> <value> THEN <expression> ELSE <expression>`".to_string()),
+                _ => None,
+            });
+            (function.map(|(span, _)| span.clone()), help)
+        },
+        CsvError::ExprError(msg) if msg == "Unknown function..." => {
+            (words.first().map(|(span, _)| span.clone()), Some("an expression starts with SUM, AVG, CALC or IF".to_string()))
+        },
+        // A cell that doesn't hold a number:
+        CsvError::ExprError(msg) if msg == "NaN" => {
+            if let Some((cell, value)) = find_cell(csv, words, |value| value.as_ref().is_ok_and(|value| value.parse::<f64>().is_err())) {
+                let value = value.unwrap_or_default();
+                let pointer = csv.get_cell_pointer(cell.0, cell.1);
+                let error = if value.is_empty() {
+                    CsvError::ExprError(format!("Type mismatch: {} is empty, not a number...", pointer))
+                }
+                else {
+                    CsvError::ExprError(format!("Type mismatch: {} holds \"{}\", not a number...", pointer, value))
+                };
+                return (error, find_word(csv, words, cell), None);
+            }
+            (None, None)
+        },
+        CsvError::CycleError(_) => {
+            let span = words.iter().find(|(_, word)| matches!(get_token(word), Some(Token::Cell(_) | Token::Range(..)))).map(|(span, _)| span.clone());
+            (span, Some("the formula reads its own value through the cells it references".to_string()))
+        },
+        _ => (None, None),
+    };
+
+    if span.is_some() {
+        return (error, span, help);
+    }
+
+    // The error may come from a cell the formula reads (with another message):
+    match find_cell(csv, words, |value| value.is_err()) {
+        Some((cell, Err(err))) => {
+            let help = format!("{} fails: {}", csv.get_cell_pointer(cell.0, cell.1), err);
+            (error, find_word(csv, words, cell), Some(help))
+        },
+        _ => (error, None, help),
+    }
+}
+
+/// Returns the first local cell pointer of the formula (also inside ranges) that matches the predicate, with its span.
+fn find_pointer(words: &[Word], predicate: impl Fn(&str) -> bool) -> Option<(Range<usize>, String)> {
+    words.iter().find_map(|(span, word)| {
+        let pointers = match get_token(word)? {
+            Token::Cell(pointer)     => vec![pointer],
+            Token::Range(start, end) => vec![start, end],
+            _ => return None,
+        };
+
+        pointers.into_iter()
+            .filter(|pointer| !pointer.contains('!') && CellRef::parse(pointer).is_some())
+            .find(|pointer| predicate(pointer))
+            .map(|pointer| (span.clone(), pointer))
+    })
+}
+
+/// Returns the first cell read by the formula whose value matches the predicate, with its value.
+fn find_cell(csv: &CSV, words: &[Word], predicate: impl Fn(&Result<String, CsvError>) -> bool) -> Option<(Cell, Result<String, CsvError>)> {
+    let formula = format!("= {}", words.iter().map(|(_, word)| *word).collect::<Vec<_>>().join(" "));
+
+    get_references(&formula, csv).into_iter().find_map(|(x, y)| {
+        let value = csv.get_evaluated_value(x, y);
+        if predicate(&value) {
+            Some(((x, y), value))
+        }
+        else {
+            None
+        }
+    })
+}
+
+/// Returns the span of the word that reads the given cell (a cell pointer or a range containing it).
+fn find_word(csv: &CSV, words: &[Word], cell: Cell) -> Option<Range<usize>> {
+    words.iter()
+        .find(|(_, word)| {
+            matches!(get_token(word), Some(Token::Cell(_) | Token::Range(..)))
+                && get_references(&format!("= {}", word), csv).contains(&cell)
+        })
+        .map(|(span, _)| span.clone())
+}
+
+/// Returns a short description of the columns of a header ("A–D" or "A, C, X").
+fn describe_header(header: &[String]) -> String {
+    let contiguous = header.windows(2).all(|pair| {
+        let (a, b) = (pair[0].chars().next(), pair[1].chars().next());
+        matches!((a, b), (Some(a), Some(b)) if b as u32 == a as u32 + 1)
+    });
+
+    match (header.first(), header.last()) {
+        (Some(first), Some(last)) if contiguous && header.len() > 2 => format!("{}–{}", first, last),
+        _ => header.join(", "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the diagnostics of a sheet given as csv contents.
+    fn check_contents(contents: &str) -> Vec<Diagnostic> {
+        check(&CSV::from_string("test.csv", contents).unwrap())
+    }
+
+    #[test]
+    fn branch_results_are_not_pointers() {
+        assert!(check_contents("A,B\n1700,= IF 1659 <= A1 THEN yes ELSE no\n").is_empty());
+        assert!(check_contents("A,B\n1,= IF A1 > 0 THEN CALC A1 + 1 ELSE no\n").is_empty());
+    }
+
+    #[test]
+    fn only_the_results_of_branches_are_skipped() {
+        // (Words that look like broken or missing pointers are texts as IF results)
+        assert!(check_contents("A,B\n1,= IF A1 > 0 THEN A_ ELSE Z9\n").is_empty());
+        assert!(check_contents("A,B:= IF A# > 0 THEN Q# ELSE no\n1,\n").is_empty());
+
+        // The condition and the arguments of function branches are still checked:
+        let diagnostics = check_contents("A,B\n1,= IF A1 > 0 THEN CALC Q1 + 1 ELSE no\n2,= IF A_ > 0 THEN yes ELSE no\n");
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(&diagnostics[0].formula[diagnostics[0].span.clone().unwrap()], "Q1");
+        assert_eq!(diagnostics[0].help.as_deref(), Some("column `Q` does not exist; header has A, B"));
+        assert_eq!(&diagnostics[1].formula[diagnostics[1].span.clone().unwrap()], "A_");
+    }
+
+    #[test]
+    fn unknown_words_are_located() {
+        let diagnostics = check_contents("A,B\n1,= CALC A_ + 1\n2,= SUMM A1 A2\n");
+        assert_eq!(diagnostics.len(), 2);
+
+        assert_eq!(diagnostics[0].cell, "B1");
+        assert!(matches!(diagnostics[0].error, CsvError::CellPError(_)));
+        assert_eq!(&diagnostics[0].formula[diagnostics[0].span.clone().unwrap()], "A_");
+
+        assert_eq!(diagnostics[1].cell, "B2");
+        assert_eq!(diagnostics[1].error.get_message(), "Unknown function SUMM...");
+        assert_eq!(&diagnostics[1].formula[diagnostics[1].span.clone().unwrap()], "SUMM");
+    }
+
+    #[test]
+    fn evaluation_errors_are_located() {
+        let diagnostics = check_contents("A,B\nx,= CALC 1 + A1\n2,= CALC Q1 + A2\n3,= CALC A9 + 1\n");
+        assert_eq!(diagnostics.len(), 3);
+
+        assert_eq!(diagnostics[0].error.get_message(), "Type mismatch: A1 holds \"x\", not a number...");
+        assert_eq!(&diagnostics[0].formula[diagnostics[0].span.clone().unwrap()], "A1");
+
+        assert_eq!(&diagnostics[1].formula[diagnostics[1].span.clone().unwrap()], "Q1");
+        assert_eq!(diagnostics[1].help.as_deref(), Some("column `Q` does not exist; header has A, B"));
+
+        assert_eq!(&diagnostics[2].formula[diagnostics[2].span.clone().unwrap()], "A9");
+        assert_eq!(diagnostics[2].help.as_deref(), Some("row 9 does not exist; the sheet has 3 rows"));
+    }
+
+    #[test]
+    fn non_ascii_pointers_are_located() {
+        let diagnostics = check_contents("A,B\n1,= CALC é1 + 1\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(&diagnostics[0].formula[diagnostics[0].span.clone().unwrap()], "é1");
+        assert_eq!(diagnostics[0].help.as_deref(), Some("column `é` does not exist; header has A, B"));
+    }
+
+    #[test]
+    fn errors_of_cells_are_located() {
+        let csv = CSV::from_string("test.csv", "A,B\nx,= CALC 1 + A1\n").unwrap();
+        let error = csv.locate_error("B1", csv.eval_cell("B1").unwrap_err());
+        assert_eq!(error.get_kind(), "ExprError");
+        assert_eq!(error.get_exit_code(), 4);
+
+        let CsvError::CellError(diagnostic) = error else { panic!("{:?}", error) };
+        assert_eq!(diagnostic.cell, "B1");
+        assert_eq!(diagnostic.formula, "= CALC 1 + A1");
+        assert_eq!(diagnostic.span, Some(11..13));
+
+        // Errors that don't come from an expression stay as they are:
+        assert!(matches!(csv.locate_error("C1", CsvError::ArgError), CsvError::ArgError));
+        assert!(matches!(csv.locate_error("A1", CsvError::ArgError), CsvError::ArgError));
+    }
+}
@@ -0,0 +1,186 @@
+use std::fmt;
+use crate::error::CsvError;
+
+/// CELL REFERENCE
+/// A parsed cell pointer with its reference modes.
+/// A `$` in front of the column or the row marks that part as absolute: `$A$1`, `$A1`, `A$1`.
+/// Absolute parts are left untouched when a formula is copied to another cell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellRef {
+    pub col: String,
+    pub row: usize,
+    pub abs_col: bool,
+    pub abs_row: bool,
+}
+
+impl CellRef {
+    /// Parses a cell pointer like "A1", "$A1", "A$1" or "$A$1".
+    /// Returns None if the input is not a cell pointer.
+    pub fn parse(cell_pointer: &str) -> Option<Self> {
+        let (abs_col, rest) = match cell_pointer.strip_prefix('$') {
+            Some(rest) => (true, rest),
+            None       => (false, cell_pointer),
+        };
+
+        // The column is always 1 character:
+        let col = rest.chars().next()?;
+        let rest = &rest[col.len_utf8()..];
+
+        let (abs_row, digits) = match rest.strip_prefix('$') {
+            Some(digits) => (true, digits),
+            None         => (false, rest),
+        };
+
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        Some(Self { col: col.to_string(), row: digits.parse().ok()?, abs_col, abs_row })
+    }
+
+    /// Returns a copy of the reference moved by the given column and row offsets.
+    /// Absolute parts are not moved.
+    /// The column offset is applied to the position of the column in the header.
+    pub fn shift(&self, header: &[String], d_col: isize, d_row: isize) -> Result<Self, CsvError> {
+        let col = if self.abs_col {
+            self.col.clone()
+        }
+        else {
+            let x = match header.iter().position(|col| col == &self.col) {
+                Some(x) => x as isize + d_col,
+                None    => return Err(CsvError::CellPError("Column index out of bounds...".to_string())),
+            };
+
+            match usize::try_from(x).ok().and_then(|x| header.get(x)) {
+                Some(col) => col.clone(),
+                None      => return Err(CsvError::CellPError("Shifted column index out of bounds...".to_string())),
+            }
+        };
+
+        Ok(Self { col, ..self.shift_row(d_row)? })
+    }
+
+    /// Returns a copy of the reference moved by the given row offset (the column stays).
+    /// An absolute row is not moved.
+    pub fn shift_row(&self, d_row: isize) -> Result<Self, CsvError> {
+        let row = if self.abs_row {
+            self.row
+        }
+        else {
+            match usize::try_from(self.row as isize + d_row) {
+                Ok(row) if row > 0 => row,
+                _ => return Err(CsvError::CellPError("Shifted row index out of bounds...".to_string())),
+            }
+        };
+
+        Ok(Self { row, ..self.clone() })
+    }
+}
+
+impl fmt::Display for CellRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let col_mark = if self.abs_col { "$" } else { "" };
+        let row_mark = if self.abs_row { "$" } else { "" };
+        write!(f, "{}{}{}{}", col_mark, self.col, row_mark, self.row)
+    }
+}
+
+/// Rewrites the cell pointers of a formula as if it was copied by the given column and row offsets.
+/// Relative references are moved, absolute references (marked with `$`) stay in place.
+/// Values that aren't formulas are returned unchanged.
+pub fn shift_formula(item: &str, header: &[String], d_col: isize, d_row: isize) -> Result<String, CsvError> {
+    if !item.starts_with('=') {
+        return Ok(item.to_string());
+    }
+
+    let words = item.split(' ')
+        .map(|word| shift_word(word, header, d_col, d_row))
+        .collect::<Result<Vec<String>, CsvError>>()?;
+
+    Ok(words.join(" "))
+}
+
+/// Shifts the cell pointers of 1 word of a formula.
+/// Handles plain cell pointers ("A1"), ranges ("A1:A5") and cell pointers of linked files ("rates.csv!B2").
+/// Pointers of linked files only move their row: their columns belong to the header of the linked file.
+pub fn shift_word(word: &str, header: &[String], d_col: isize, d_row: isize) -> Result<String, CsvError> {
+    // Numbers are never cell pointers:
+    if word.parse::<f64>().is_ok() {
+        return Ok(word.to_string());
+    }
+
+    let (file, cells) = match word.split_once('!') {
+        Some((file, cells)) => (format!("{}!", file), cells),
+        None                => (String::new(), word),
+    };
+
+    let cells = cells.split(':').map(|cell| {
+        match CellRef::parse(cell) {
+            Some(cell_ref) if !file.is_empty() => Ok(cell_ref.shift_row(d_row)?.to_string()),
+            Some(cell_ref) if header.contains(&cell_ref.col) => Ok(cell_ref.shift(header, d_col, d_row)?.to_string()),
+            _ => Ok(cell.to_string()),
+        }
+    }).collect::<Result<Vec<String>, CsvError>>()?;
+
+    Ok(format!("{}{}", file, cells.join(":")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv::CSV;
+
+    fn header() -> Vec<String> {
+        ["A", "B", "C"].iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn cell_pointers_keep_their_modes() {
+        assert_eq!(CellRef::parse("$A12"), Some(CellRef { col: "A".to_string(), row: 12, abs_col: true, abs_row: false }));
+        assert_eq!(CellRef::parse("é$3").map(|cell| cell.to_string()).as_deref(), Some("é$3"));
+        for pointer in ["", "A", "$$A1", "A1x", "A-1"] {
+            assert_eq!(CellRef::parse(pointer), None, "{:?}", pointer);
+        }
+    }
+
+    #[test]
+    fn relative_references_are_shifted() {
+        let shift = |item| shift_formula(item, &header(), 1, 2);
+        assert_eq!(shift("= CALC A1 * $A$1 + A$1 - $A1").unwrap(), "= CALC B3 * $A$1 + B$1 - $A3");
+        assert_eq!(shift("= SUM A1:A2 rates.csv!B1 rates.csv!$Z$1").unwrap(), "= SUM B3:B4 rates.csv!B3 rates.csv!$Z$1");
+        // (Values and words that aren't pointers of the header stay)
+        assert_eq!(shift("A1").unwrap(), "A1");
+        assert_eq!(shift("= IF A1 > 1.5 THEN yes ELSE Z1").unwrap(), "= IF B3 > 1.5 THEN yes ELSE Z1");
+
+        assert!(matches!(shift_formula("= CALC C1", &header(), 1, 0), Err(CsvError::CellPError(_))));
+        assert!(matches!(shift_formula("= CALC A1", &header(), 0, -1), Err(CsvError::CellPError(_))));
+    }
+
+    #[test]
+    fn fill_copies_formulas() {
+        let mut csv = CSV::from_string("test.csv", "A,B,C\n1,2,= CALC A1 * $B$1\n3,4,\n5,6,\n").unwrap();
+        csv.fill("C1", "C2", "C3").unwrap();
+        assert_eq!(csv.get("C3").unwrap(), "= CALC A3 * $B$1");
+        assert_eq!(csv.get_cell_value("C3").unwrap(), "10");
+
+        // (A copy that would leave the table changes nothing)
+        assert!(csv.fill("C3", "A2", "C2").is_err());
+        assert_eq!(csv.get("A2").unwrap(), "3");
+    }
+
+    #[test]
+    fn fill_moves_the_rows_of_linked_cells() {
+        let dir = std::env::temp_dir().join(format!("echcell_{}_fill_links", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("rates.csv"), "Y,Z\nusd,2\neur,3\nchf,4\n").unwrap();
+        std::fs::write(dir.join("data.csv"), "A,B\n1,= CALC A1 * rates.csv!Z1\n2,\n3,\n").unwrap();
+
+        let mut csv = CSV::new(dir.join("data.csv").display().to_string()).unwrap();
+        csv.fill("B1", "B2", "B3").unwrap();
+        assert_eq!(csv.get("B3").unwrap(), "= CALC A3 * rates.csv!Z3");
+        assert_eq!(csv.get_range_values("B1", "B3").unwrap(), vec!["2", "6", "12"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,875 @@
+use std::io::{Cursor, Write};
+use std::path::Path;
+use calamine::{Reader, open_workbook_auto};
+use zip::{CompressionMethod, ZipWriter};
+use zip::write::SimpleFileOptions;
+use crate::csv::CSV;
+use crate::error::CsvError;
+use super::exprs::{Token, Functions};
+use super::refs::CellRef;
+
+/// SPREADSHEET FORMAT
+/// The file formats of spreadsheet applications a sheet can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpreadsheetFormat {
+    Xlsx,
+    Ods,
+}
+
+impl SpreadsheetFormat {
+    /// Returns the format with the given name ("xlsx" or "ods").
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "xlsx" => Some(Self::Xlsx),
+            "ods"  => Some(Self::Ods),
+            _ => None,
+        }
+    }
+
+    /// Returns the format that belongs to the extension of a path (None for other extensions).
+    pub fn from_path(path: &str) -> Option<Self> {
+        Self::from_name(Path::new(path).extension()?.to_str()?)
+    }
+
+    /// Returns the file extension of the format.
+    pub fn get_extension(&self) -> &'static str {
+        match self {
+            Self::Xlsx => "xlsx",
+            Self::Ods  => "ods",
+        }
+    }
+}
+
+/// A cell of an exported sheet.
+enum SheetCell {
+    Number(f64),
+    Text(String),
+    // Translated formula with the cached value of the expression:
+    Formula(String, Result<String, CsvError>),
+}
+
+/// Returns the spreadsheet name of a column index (0 -> "A", 25 -> "Z", 26 -> "AA").
+fn get_column_name(mut index: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (index % 26) as u8);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap_or_default()
+}
+
+/// Translates an echcell expression into the formula syntax of a spreadsheet format:
+/// `= SUM A1 A5` becomes `=SUM(A2:A6)` (xlsx) or `=SUM([.A2:.A6])` (ods), `= CALC A1 * 2` becomes `=A2*2`
+/// and `= IF A1 > 3 THEN SUM A1 A5 ELSE 0` becomes `=IF(A2>3,SUM(A2:A6),0)`.
+/// Cell pointers are moved to the cells of the exported sheet: the columns are numbered from A in the order of the header
+/// and the rows move down by 1 (the first row of the spreadsheet holds the header).
+/// Returns None for expressions that can't be translated (cells of linked files, invalid expressions).
+pub fn to_native_formula(header: &[String], item: &str, format: SpreadsheetFormat) -> Option<String> {
+    let expr = item.strip_prefix('=')?;
+    let dialect = Dialect { header, format };
+
+    Some(format!("={}", dialect.translate(&Token::tokenize(expr))?))
+}
+
+/// What a translation needs: the header of the sheet and the target format.
+struct Dialect<'a> {
+    header: &'a [String],
+    format: SpreadsheetFormat,
+}
+
+impl Dialect<'_> {
+    /// Translates the tokens of an expression (without the leading '=').
+    fn translate(&self, tokens: &[Token]) -> Option<String> {
+        let (func, args) = tokens.split_first()?;
+
+        match func {
+            Token::Func(Functions::Sum | Functions::Avg) => {
+                let (start, end) = match args {
+                    [Token::Range(start, end)]             => (start, end),
+                    [Token::Cell(start), Token::Cell(end)] => (start, end),
+                    _ => return None,
+                };
+                let name = if matches!(func, Token::Func(Functions::Sum)) { "SUM" } else { "AVERAGE" };
+                Some(format!("{}({})", name, self.translate_range(start, end)?))
+            },
+            Token::Func(Functions::Calc) => {
+                args.iter()
+                    .map(|token| match token {
+                        Token::Number(n)    => Some(n.to_string()),
+                        Token::Cell(cell)   => self.translate_cell(cell),
+                        Token::Operator(op) => Some(op.get_symbol().to_string()),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<String>>>()
+                    .map(|parts| parts.concat())
+            },
+            Token::Func(Functions::If) => {
+                let parts: Vec<&[Token]> = args.split(|token| matches!(token, Token::Then | Token::Else)).collect();
+                let [condition, then, otherwise] = parts.as_slice() else {
+                    return None;
+                };
+
+                let condition = match condition {
+                    [left, Token::CmpOperator(cmp), right] => {
+                        // (`==` is `=` in spreadsheets)
+                        let symbol = if cmp.get_symbol() == "==" { "=" } else { cmp.get_symbol() };
+                        format!("{}{}{}", self.translate_operand(left)?, symbol, self.translate_operand(right)?)
+                    },
+                    _ => return None,
+                };
+                let separator = match self.format {
+                    SpreadsheetFormat::Xlsx => ",",
+                    SpreadsheetFormat::Ods  => ";",
+                };
+
+                Some(format!("IF({}{}{}{}{})", condition, separator, self.translate_branch(then)?, separator, self.translate_branch(otherwise)?))
+            },
+            _ => None,
+        }
+    }
+
+    /// Translates a value of an IF condition (a number or a cell).
+    fn translate_operand(&self, token: &Token) -> Option<String> {
+        match token {
+            Token::Number(n)  => Some(n.to_string()),
+            Token::Cell(cell) => self.translate_cell(cell),
+            _ => None,
+        }
+    }
+
+    /// Translates a branch of IF the way `func_if` evaluates it
+    /// (a cell pointer in a branch is returned as text, not as the value of the cell).
+    fn translate_branch(&self, tokens: &[Token]) -> Option<String> {
+        match tokens.first()? {
+            Token::Number(n)  => Some(n.to_string()),
+            Token::Cell(text) => Some(format!("\"{}\"", text.replace('"', "\"\""))),
+            Token::Func(_)    => self.translate(tokens),
+            _ => None,
+        }
+    }
+
+    /// Translates a cell pointer ("[.A1]" in ods).
+    fn translate_cell(&self, cell_pointer: &str) -> Option<String> {
+        let cell = self.translate_pointer(cell_pointer)?;
+        match self.format {
+            SpreadsheetFormat::Xlsx => Some(cell),
+            SpreadsheetFormat::Ods  => Some(format!("[.{}]", cell)),
+        }
+    }
+
+    /// Translates a range ("A1:A5", "[.A1:.A5]" in ods).
+    fn translate_range(&self, start: &str, end: &str) -> Option<String> {
+        let (start, end) = (self.translate_pointer(start)?, self.translate_pointer(end)?);
+        match self.format {
+            SpreadsheetFormat::Xlsx => Some(format!("{}:{}", start, end)),
+            SpreadsheetFormat::Ods  => Some(format!("[.{}:.{}]", start, end)),
+        }
+    }
+
+    /// Moves a cell pointer of the sheet to its exported cell (`$` markers are kept).
+    /// Returns None for cells of linked files and columns that aren't in the header.
+    fn translate_pointer(&self, cell_pointer: &str) -> Option<String> {
+        let cell = CellRef::parse(cell_pointer)?;
+        let x = self.header.iter().position(|name| *name == cell.col)?;
+
+        Some(format!(
+            "{}{}{}{}",
+            if cell.abs_col { "$" } else { "" },
+            get_column_name(x),
+            if cell.abs_row { "$" } else { "" },
+            cell.row + 1,
+        ))
+    }
+}
+
+/// Evaluates a CSV object and writes it as a spreadsheet file to a writer.
+/// The first row holds the header as it's written in a csv file (column formulas included, so they can be read back),
+/// the body follows from the second row on.
+/// Expressions are written as native formulas together with their value, so the spreadsheet application shows the values
+/// right away and can recalculate them; expressions that can't be translated (see `to_native_formula`) are written as values.
+/// Cells of column formulas are written as native formulas too.
+/// Returns a Result type of () or a CsvError::FileError() if writing failed.
+pub fn write_spreadsheet(csv: &CSV, format: SpreadsheetFormat, writer: &mut impl Write) -> Result<(), CsvError> {
+    let header: Vec<(usize, SheetCell)> = csv.get_source_header().into_iter().enumerate()
+        .filter(|(_, name)| !name.is_empty())
+        .map(|(x, name)| (x, SheetCell::Text(name)))
+        .collect();
+
+    let body = (0..csv.get_row_count())
+        .map(|y| {
+            (0..csv.get_row_width(y))
+                .filter_map(|x| {
+                    let item = csv.get_cell_source(x, y);
+                    if item.is_empty() || x >= csv.header.len() {
+                        return None;
+                    }
+
+                    let value = if item.starts_with('=') { csv.get_evaluated_value(x, y) } else { Ok(item.clone()) };
+                    let cell = match (to_native_formula(&csv.header, &item, format), value) {
+                        (Some(formula), value) => SheetCell::Formula(formula, value),
+                        (None, Ok(value)) => match value.parse::<f64>() {
+                            Ok(n) if n.is_finite() => SheetCell::Number(n),
+                            _ => SheetCell::Text(value),
+                        },
+                        (None, Err(err)) => SheetCell::Text(err.to_string()),
+                    };
+                    Some((x, cell))
+                })
+                .collect()
+        });
+    let rows: Vec<Vec<(usize, SheetCell)>> = [header].into_iter().chain(body).collect();
+
+    let sheet_name = get_sheet_name(&csv.file);
+    let files = match format {
+        SpreadsheetFormat::Xlsx => get_xlsx_files(&sheet_name, &rows),
+        SpreadsheetFormat::Ods  => get_ods_files(&sheet_name, &rows),
+    };
+
+    // Packing the files:
+    // (The first file of an ods package has to be stored without compression)
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let packed = files.iter().try_for_each(|(name, contents)| {
+        let method = if *name == "mimetype" { CompressionMethod::Stored } else { CompressionMethod::Deflated };
+        zip.start_file(*name, SimpleFileOptions::default().compression_method(method))?;
+        zip.write_all(contents.as_bytes())?;
+        Ok::<(), zip::result::ZipError>(())
+    });
+
+    match packed.and_then(|_| zip.finish()) {
+        Ok(cursor) => match writer.write_all(&cursor.into_inner()) {
+            Ok(_)  => Ok(()),
+            Err(_) => Err(CsvError::FileError("Could not write to output file...".to_string())),
+        },
+        Err(_) => Err(CsvError::FileError("Could not create spreadsheet file...".to_string())),
+    }
+}
+
+/// Returns the name of the exported sheet: the file name without the extension
+/// (without the characters spreadsheet applications don't allow, at most 31 characters).
+fn get_sheet_name(file: &str) -> String {
+    let stem = Path::new(file).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let name: String = stem.chars().filter(|c| !"[]:*?/\\".contains(*c)).take(31).collect();
+
+    if name.is_empty() {
+        "Sheet1".to_string()
+    }
+    else {
+        name
+    }
+}
+
+/// Returns the files of an xlsx package (a SpreadsheetML workbook with 1 sheet).
+fn get_xlsx_files(sheet_name: &str, rows: &[Vec<(usize, SheetCell)>]) -> Vec<(&'static str, String)> {
+    let mut sheet = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+        <worksheet xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\"><sheetData>",
+    );
+
+    rows.iter().enumerate().filter(|(_, row)| !row.is_empty()).for_each(|(y, row)| {
+        sheet.push_str(&format!("<row r=\"{}\">", y + 1));
+        row.iter().for_each(|(x, cell)| {
+            let pointer = format!("{}{}", get_column_name(*x), y + 1);
+            sheet.push_str(&match cell {
+                SheetCell::Number(n) => format!("<c r=\"{}\"><v>{}</v></c>", pointer, n),
+                SheetCell::Text(text) => format!("<c r=\"{}\" t=\"inlineStr\"><is><t>{}</t></is></c>", pointer, get_xml_text(text)),
+                SheetCell::Formula(formula, value) => {
+                    let formula = get_xml_text(&formula[1..]);
+                    match value.as_ref().map(|value| value.parse::<f64>()) {
+                        Ok(Ok(n)) if n.is_finite() => format!("<c r=\"{}\"><f>{}</f><v>{}</v></c>", pointer, formula, n),
+                        Ok(_) => format!(
+                            "<c r=\"{}\" t=\"str\"><f>{}</f><v>{}</v></c>", pointer, formula, get_xml_text(value.as_deref().unwrap_or_default()),
+                        ),
+                        Err(_) => format!("<c r=\"{}\" t=\"e\"><f>{}</f><v>#VALUE!</v></c>", pointer, formula),
+                    }
+                },
+            });
+        });
+        sheet.push_str("</row>");
+    });
+    sheet.push_str("</sheetData></worksheet>");
+
+    vec![
+        ("[Content_Types].xml", "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+            <Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+            <Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+            <Default Extension=\"xml\" ContentType=\"application/xml\"/>\
+            <Override PartName=\"/xl/workbook.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml\"/>\
+            <Override PartName=\"/xl/worksheets/sheet1.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml\"/>\
+            </Types>".to_string()),
+        ("_rels/.rels", "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+            <Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+            <Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument\" Target=\"xl/workbook.xml\"/>\
+            </Relationships>".to_string()),
+        ("xl/workbook.xml", format!("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+            <workbook xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\" \
+            xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\">\
+            <sheets><sheet name=\"{}\" sheetId=\"1\" r:id=\"rId1\"/></sheets></workbook>", get_xml_text(sheet_name))),
+        ("xl/_rels/workbook.xml.rels", "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+            <Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+            <Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet\" Target=\"worksheets/sheet1.xml\"/>\
+            </Relationships>".to_string()),
+        ("xl/worksheets/sheet1.xml", sheet),
+    ]
+}
+
+/// Returns the files of an ods package (an OpenDocument spreadsheet with 1 table).
+fn get_ods_files(sheet_name: &str, rows: &[Vec<(usize, SheetCell)>]) -> Vec<(&'static str, String)> {
+    let mut table = format!("<table:table table:name=\"{}\">", get_xml_text(sheet_name));
+
+    // (Cells are positional: gaps are filled with repeated empty cells and rows)
+    let mut empty_rows = 0;
+    rows.iter().for_each(|row| {
+        if row.is_empty() {
+            empty_rows += 1;
+            return;
+        }
+        if empty_rows > 0 {
+            table.push_str(&format!("<table:table-row table:number-rows-repeated=\"{}\"><table:table-cell/></table:table-row>", empty_rows));
+            empty_rows = 0;
+        }
+
+        table.push_str("<table:table-row>");
+        let mut next = 0;
+        row.iter().for_each(|(x, cell)| {
+            if *x > next {
+                table.push_str(&format!("<table:table-cell table:number-columns-repeated=\"{}\"/>", x - next));
+            }
+            next = x + 1;
+
+            table.push_str(&match cell {
+                SheetCell::Number(n) => format!(
+                    "<table:table-cell office:value-type=\"float\" office:value=\"{}\"><text:p>{}</text:p></table:table-cell>", n, n,
+                ),
+                SheetCell::Text(text) => format!(
+                    "<table:table-cell office:value-type=\"string\"><text:p>{}</text:p></table:table-cell>", get_xml_text(text),
+                ),
+                SheetCell::Formula(formula, value) => {
+                    let formula = get_xml_text(&format!("of:{}", formula));
+                    match value.as_ref().map(|value| value.parse::<f64>()) {
+                        Ok(Ok(n)) if n.is_finite() => format!(
+                            "<table:table-cell table:formula=\"{}\" office:value-type=\"float\" office:value=\"{}\"><text:p>{}</text:p></table:table-cell>",
+                            formula, n, n,
+                        ),
+                        Ok(_) => format!(
+                            "<table:table-cell table:formula=\"{}\" office:value-type=\"string\" office:string-value=\"{}\"><text:p>{}</text:p></table:table-cell>",
+                            formula, get_xml_text(value.as_deref().unwrap_or_default()), get_xml_text(value.as_deref().unwrap_or_default()),
+                        ),
+                        Err(_) => format!("<table:table-cell table:formula=\"{}\"><text:p>#VALUE!</text:p></table:table-cell>", formula),
+                    }
+                },
+            });
+        });
+        table.push_str("</table:table-row>");
+    });
+    table.push_str("</table:table>");
+
+    vec![
+        ("mimetype", "application/vnd.oasis.opendocument.spreadsheet".to_string()),
+        ("META-INF/manifest.xml", "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <manifest:manifest xmlns:manifest=\"urn:oasis:names:tc:opendocument:xmlns:manifest:1.0\" manifest:version=\"1.2\">\
+            <manifest:file-entry manifest:full-path=\"/\" manifest:version=\"1.2\" manifest:media-type=\"application/vnd.oasis.opendocument.spreadsheet\"/>\
+            <manifest:file-entry manifest:full-path=\"content.xml\" manifest:media-type=\"text/xml\"/>\
+            </manifest:manifest>".to_string()),
+        ("content.xml", format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <office:document-content xmlns:office=\"urn:oasis:names:tc:opendocument:xmlns:office:1.0\" \
+            xmlns:table=\"urn:oasis:names:tc:opendocument:xmlns:table:1.0\" \
+            xmlns:text=\"urn:oasis:names:tc:opendocument:xmlns:text:1.0\" \
+            xmlns:of=\"urn:oasis:names:tc:opendocument:xmlns:of:1.2\" office:version=\"1.2\">\
+            <office:body><office:spreadsheet>{}</office:spreadsheet></office:body></office:document-content>", table)),
+    ]
+}
+
+/// Returns a text with the XML special characters escaped.
+fn get_xml_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+/// A part of a native formula.
+#[derive(Debug, Clone, PartialEq)]
+enum NativeToken {
+    Number(f64),
+    Text(String),
+    Cell(String),
+    Range(String, String),
+    Name(String),
+    Open,
+    Close,
+    Separator,
+    Operator(char),
+    CmpOperator(&'static str),
+}
+
+/// Translates a native formula of an xlsx or ods file into an echcell expression (the reverse of `to_native_formula`):
+/// `=SUM(A2:A6)` or `of:=SUM([.A2:.A6])` becomes `= SUM A1 A5`, `=AVERAGE(A2:A6)` becomes `= AVG A1 A5`,
+/// `=A2*2` becomes `= CALC A1 * 2` and `=IF(A2>3,SUM(A2:A6),0)` becomes `= IF A1 > 3 THEN SUM A1 A5 ELSE 0`.
+/// The first row of the spreadsheet holds the given header, so cell pointers move up by 1 row and their columns get the names of the header.
+/// Returns None for formulas echcell can't express (other functions, parentheses, other sheets, cells of the header row...).
+pub fn from_native_formula(header: &[String], formula: &str) -> Option<String> {
+    let formula = formula.strip_prefix("of:").unwrap_or(formula);
+    let formula = formula.strip_prefix('=').unwrap_or(formula);
+
+    Some(format!("= {}", translate_native(&get_native_tokens(formula, header)?)?))
+}
+
+/// Splits a native formula (without the leading '=') into its parts (cell pointers are moved to the sheet with the given header).
+/// Returns None if the formula has anything that can't be translated.
+fn get_native_tokens(formula: &str, header: &[String]) -> Option<Vec<NativeToken>> {
+    let chars: Vec<char> = formula.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        i += 1;
+
+        match c {
+            _ if c.is_whitespace() => {},
+            '(' => tokens.push(NativeToken::Open),
+            ')' => tokens.push(NativeToken::Close),
+            // (Arguments are separated by ',' in xlsx and by ';' in ods)
+            ',' | ';' => tokens.push(NativeToken::Separator),
+            '+' | '-' | '*' | '/' => tokens.push(NativeToken::Operator(c)),
+            '=' => tokens.push(NativeToken::CmpOperator("==")),
+            '<' | '>' => {
+                let symbol = match (c, chars.get(i)) {
+                    ('<', Some('=')) => "<=",
+                    ('>', Some('=')) => ">=",
+                    ('<', Some('>')) => return None,
+                    ('<', _) => "<",
+                    _ => ">",
+                };
+                i += symbol.len() - 1;
+                tokens.push(NativeToken::CmpOperator(symbol));
+            },
+            '"' => {
+                // (A quote inside a text is written twice)
+                let mut text = String::new();
+                loop {
+                    match (chars.get(i), chars.get(i + 1)) {
+                        (Some('"'), Some('"')) => {
+                            text.push('"');
+                            i += 2;
+                        },
+                        (Some('"'), _) => break,
+                        (Some(c), _) => {
+                            text.push(*c);
+                            i += 1;
+                        },
+                        (None, _) => return None,
+                    }
+                }
+                i += 1;
+                tokens.push(NativeToken::Text(text));
+            },
+            '[' => {
+                // Ods references ("[.A1]", "[.A1:.A5]"; references with a sheet name are of other sheets):
+                let end = start + chars[start..].iter().position(|c| *c == ']')?;
+                let reference: String = chars[start + 1..end].iter().collect();
+                i = end + 1;
+
+                let pointers = reference.split(':')
+                    .map(|pointer| get_native_pointer(pointer.strip_prefix('.')?, header))
+                    .collect::<Option<Vec<String>>>()?;
+                match pointers.as_slice() {
+                    [cell]       => tokens.push(NativeToken::Cell(cell.clone())),
+                    [start, end] => tokens.push(NativeToken::Range(start.clone(), end.clone())),
+                    _ => return None,
+                }
+            },
+            _ if c.is_ascii_digit() || c == '.' => {
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.'
+                    || (matches!(chars[i], 'e' | 'E') && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit() || *c == '-' || *c == '+'))
+                    || (matches!(chars[i], '-' | '+') && matches!(chars[i - 1], 'e' | 'E')))
+                {
+                    i += 1;
+                }
+                let number: String = chars[start..i].iter().collect();
+                tokens.push(NativeToken::Number(number.parse().ok()?));
+            },
+            _ if c.is_ascii_alphabetic() || c == '$' || c == '_' => {
+                // Function names and xlsx references ("A1", "$A$1", "A1:A5"):
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || matches!(chars[i], '$' | '_' | '.')) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+
+                if chars.get(i) == Some(&'(') {
+                    // (Newer functions are written with a prefix in xlsx files)
+                    tokens.push(NativeToken::Name(word.trim_start_matches("_xlfn.").to_uppercase()));
+                }
+                else if chars.get(i) == Some(&':') {
+                    let end = i + 1 + chars[i + 1..].iter().take_while(|c| c.is_ascii_alphanumeric() || **c == '$').count();
+                    let end_word: String = chars[i + 1..end].iter().collect();
+                    i = end;
+                    tokens.push(NativeToken::Range(get_native_pointer(&word, header)?, get_native_pointer(&end_word, header)?));
+                }
+                else {
+                    tokens.push(NativeToken::Cell(get_native_pointer(&word, header)?));
+                }
+            },
+            _ => return None,
+        }
+    }
+
+    Some(tokens)
+}
+
+/// Returns the cell pointer of a spreadsheet cell in the sheet with the given header (`$` markers are kept):
+/// the column gets its name from the header and the row moves up by 1 ("C3" -> "x2" with the header "A,B,x").
+/// Returns None for cells echcell can't read (columns after Z or without a 1 character name, cells of the header row).
+fn get_native_pointer(pointer: &str, header: &[String]) -> Option<String> {
+    let cell = CellRef::parse(pointer)?;
+    let x = match cell.col.as_bytes() {
+        [c] if c.is_ascii_uppercase() => (c - b'A') as usize,
+        _ => return None,
+    };
+    if cell.row < 2 {
+        return None;
+    }
+
+    let pointer = CellRef { col: header.get(x)?.clone(), row: cell.row - 1, abs_col: cell.abs_col, abs_row: cell.abs_row }.to_string();
+    match CellRef::parse(&pointer) {
+        Some(parsed) if parsed.col == header[x] => Some(pointer),
+        _ => None,
+    }
+}
+
+/// Translates the parts of a native formula into the words of an echcell expression (without the leading '=').
+fn translate_native(tokens: &[NativeToken]) -> Option<String> {
+    match tokens {
+        [NativeToken::Name(name), NativeToken::Open, args @ .., NativeToken::Close] => {
+            let args = get_native_args(args)?;
+            match (name.as_str(), args.as_slice()) {
+                ("SUM" | "AVERAGE", [range]) => {
+                    let (start, end) = match range {
+                        [NativeToken::Range(start, end)] => (start, end),
+                        [NativeToken::Cell(cell)]        => (cell, cell),
+                        _ => return None,
+                    };
+                    Some(format!("{} {} {}", if name == "SUM" { "SUM" } else { "AVG" }, start, end))
+                },
+                ("IF", [condition, then, otherwise]) => {
+                    let i = condition.iter().position(|token| matches!(token, NativeToken::CmpOperator(_)))?;
+                    let NativeToken::CmpOperator(symbol) = condition[i] else {
+                        return None;
+                    };
+                    let condition = format!(
+                        "{} {} {}", translate_native_operand(&condition[..i])?, symbol, translate_native_operand(&condition[i + 1..])?,
+                    );
+                    Some(format!("IF {} THEN {} ELSE {}", condition, translate_native_branch(then)?, translate_native_branch(otherwise)?))
+                },
+                _ => None,
+            }
+        },
+        _ => {
+            // Math expressions (numbers and cells joined by operators, no parentheses):
+            let mut words = Vec::new();
+            let mut rest = tokens;
+            loop {
+                let (operand, after) = match rest {
+                    [NativeToken::Operator('-'), NativeToken::Number(n), after @ ..] => ((-n).to_string(), after),
+                    [NativeToken::Number(n), after @ ..] => (n.to_string(), after),
+                    [NativeToken::Cell(cell), after @ ..] => (cell.clone(), after),
+                    _ => return None,
+                };
+                words.push(operand);
+
+                match after {
+                    [] => break,
+                    [NativeToken::Operator(op), after @ ..] => {
+                        words.push(op.to_string());
+                        rest = after;
+                    },
+                    _ => return None,
+                }
+            }
+            Some(format!("CALC {}", words.join(" ")))
+        },
+    }
+}
+
+/// Splits the arguments of a native function at its separators.
+/// Returns None if the parentheses don't match (the arguments belong to more than 1 call).
+fn get_native_args(tokens: &[NativeToken]) -> Option<Vec<&[NativeToken]>> {
+    let mut args = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            NativeToken::Open => depth += 1,
+            NativeToken::Close if depth == 0 => return None,
+            NativeToken::Close => depth -= 1,
+            NativeToken::Separator if depth == 0 => {
+                args.push(&tokens[start..i]);
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    if depth != 0 {
+        return None;
+    }
+
+    args.push(&tokens[start..]);
+    Some(args)
+}
+
+/// Translates a value of an IF condition (a number or a cell).
+fn translate_native_operand(tokens: &[NativeToken]) -> Option<String> {
+    match tokens {
+        [NativeToken::Number(n)] => Some(n.to_string()),
+        [NativeToken::Operator('-'), NativeToken::Number(n)] => Some((-n).to_string()),
+        [NativeToken::Cell(cell)] => Some(cell.clone()),
+        _ => None,
+    }
+}
+
+/// Translates a branch of IF the way `func_if` evaluates it:
+/// a text is returned as it is (only texts of 1 word that aren't keywords, numbers or ranges can be written),
+/// a number is written as it is and a cell is read with CALC (a cell pointer in a branch would be returned as text).
+fn translate_native_branch(tokens: &[NativeToken]) -> Option<String> {
+    match tokens {
+        [NativeToken::Number(n)] => Some(n.to_string()),
+        [NativeToken::Text(text)] => match Token::tokenize(text).as_slice() {
+            [Token::Cell(word)] if word == text => Some(text.clone()),
+            _ => None,
+        },
+        [NativeToken::Cell(cell)] => Some(format!("CALC {}", cell)),
+        _ => translate_native(tokens),
+    }
+}
+
+impl CSV {
+    /// Loads the first sheet (or the sheet with the given name) of an xlsx or ods file.
+    /// The first row is the header (like the first line of a csv file, so it can declare column formulas: `b:= CALC a# * 2`)
+    /// and the rows below it are the body. Header cells that are empty are named after their spreadsheet column.
+    /// Native formulas are translated into echcell expressions (see `from_native_formula`);
+    /// cells with formulas that can't be translated keep the value the spreadsheet application calculated for them.
+    /// Cells whose formula is the column formula of their column are left empty, so the column formula applies to them again.
+    /// (Commas and line breaks inside texts are replaced by spaces: csv items aren't quoted)
+    /// The sheet is named like the file with a ".csv" extension, so `save` doesn't overwrite the spreadsheet.
+    /// Returns a Result type of the CSV object with the cell pointers (of the spreadsheet) and formulas that weren't translated,
+    /// or a CsvError::FileError() if the file or the sheet can't be read.
+    pub fn from_spreadsheet(path: &str, sheet: Option<&str>) -> Result<(Self, Vec<(String, String)>), CsvError> {
+        let mut workbook = match open_workbook_auto(path) {
+            Ok(workbook) => workbook,
+            Err(_) => return Err(CsvError::FileError(format!("Could not open spreadsheet file {}...", path))),
+        };
+
+        // Finding the sheet:
+        let names = workbook.sheet_names();
+        let name = match sheet {
+            Some(sheet) if names.iter().any(|name| name == sheet) => sheet.to_string(),
+            Some(sheet) => return Err(CsvError::FileError(format!("{} has no sheet named {}...", path, sheet))),
+            None => match names.first() {
+                Some(name) => name.clone(),
+                None       => return Err(CsvError::FileError(format!("{} has no sheets...", path))),
+            },
+        };
+
+        let (values, formulas) = match (workbook.worksheet_range(&name), workbook.worksheet_formula(&name)) {
+            (Ok(values), Ok(formulas)) => (values, formulas),
+            _ => return Err(CsvError::FileError(format!("Could not read sheet {} of {}...", name, path))),
+        };
+
+        // Sizing the sheet:
+        // (Ranges only span the used cells, their positions are absolute)
+        let (height, width) = [values.end(), formulas.end()].into_iter().flatten()
+            .fold((0, 0), |(height, width), (y, x)| (height.max(y as usize + 1), width.max(x as usize + 1)));
+
+        let mut rows = vec![vec![String::new(); width]; height.max(1)];
+        let (y_start, x_start) = values.start().unwrap_or_default();
+        values.used_cells().for_each(|(y, x, value)| {
+            rows[y_start as usize + y][x_start as usize + x] = value.to_string().replace([',', '\n', '\r'], " ");
+        });
+
+        // Separating the header from the body:
+        let raw_header: Vec<String> = rows.remove(0).into_iter().enumerate()
+            .map(|(x, item)| if item.trim().is_empty() { get_column_name(x) } else { item })
+            .collect();
+        let (header, column_formulas) = Self::parse_header(raw_header.clone());
+        let mut body = rows;
+
+        // Translating the formulas:
+        // (Formulas of the header row can't be expressed, the header only has names)
+        let mut unsupported = Vec::new();
+        let (y_start, x_start) = formulas.start().unwrap_or_default();
+        formulas.used_cells().filter(|(_, _, formula)| !formula.is_empty()).for_each(|(y, x, formula)| {
+            let (y, x) = (y_start as usize + y, x_start as usize + x);
+            match from_native_formula(&header, formula).filter(|_| y > 0) {
+                // (Cells of a column formula are left to it)
+                Some(expr) => body[y - 1][x] = match &column_formulas[x] {
+                    Some(column_formula) if is_same_formula(&column_formula.replace('#', &y.to_string()), &expr) => String::new(),
+                    _ => expr,
+                },
+                None => {
+                    // (Reported formulas are written the same way for both formats: "=VLOOKUP(...)")
+                    let formula = formula.strip_prefix("of:").unwrap_or(formula).trim_start_matches('=');
+                    unsupported.push((format!("{}{}", get_column_name(x), y + 1), format!("={}", formula)));
+                },
+            }
+        });
+
+        let file = Path::new(path).with_extension("csv").display().to_string();
+        Ok((Self::from_rows(&file, raw_header, body), unsupported))
+    }
+}
+
+/// Returns whether 2 echcell expressions are the same, apart from their whitespace.
+fn is_same_formula(a: &str, b: &str) -> bool {
+    a.split_whitespace().eq(b.split_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::{Data, open_workbook_auto_from_rs};
+
+    /// Exports a sheet and returns the values of its first sheet (1 line per row, cells separated by '|')
+    /// and its formulas ("D2: C2*2").
+    fn export(csv: &CSV, format: SpreadsheetFormat) -> (Vec<String>, Vec<String>) {
+        let mut file = Vec::new();
+        write_spreadsheet(csv, format, &mut file).unwrap();
+
+        let mut workbook = open_workbook_auto_from_rs(Cursor::new(file)).unwrap();
+        let name = workbook.sheet_names()[0].clone();
+        let values = workbook.worksheet_range(&name).unwrap();
+        let formulas = workbook.worksheet_formula(&name).unwrap();
+
+        let values = values.rows().map(|row| row.iter().map(Data::to_string).collect::<Vec<_>>().join("|")).collect();
+        let (y_start, x_start) = formulas.start().unwrap_or_default();
+        let formulas = formulas.used_cells()
+            .filter(|(_, _, formula)| !formula.is_empty())
+            .map(|(y, x, formula)| format!("{}{}: {}", get_column_name(x_start as usize + x), y_start as usize + y + 1, formula))
+            .collect();
+        (values, formulas)
+    }
+
+    #[test]
+    fn formulas_are_translated() {
+        let header: Vec<String> = ["A", "B", "x"].iter().map(|name| name.to_string()).collect();
+        let translate = |item| to_native_formula(&header, item, SpreadsheetFormat::Xlsx);
+
+        assert_eq!(translate("= SUM A1 A5").as_deref(), Some("=SUM(A2:A6)"));
+        assert_eq!(translate("= AVG $x$1:x3").as_deref(), Some("=AVERAGE($C$2:C4)"));
+        assert_eq!(translate("= CALC x1 * 2 - B1").as_deref(), Some("=C2*2-B2"));
+        assert_eq!(translate("= IF A1 == 3 THEN yes ELSE SUM A1 A2").as_deref(), Some("=IF(A2=3,\"yes\",SUM(A2:A3))"));
+        assert_eq!(
+            to_native_formula(&header, "= IF A1 > 3 THEN 1 ELSE 0", SpreadsheetFormat::Ods).as_deref(),
+            Some("=IF([.A2]>3;1;0)"),
+        );
+        assert_eq!(translate("= CALC rates.csv!A1 * 2"), None);
+        assert_eq!(translate("= CALC Q1 * 2"), None);
+    }
+
+    #[test]
+    fn the_header_is_the_first_row() {
+        let csv = CSV::from_string("prices.csv", "Price,Qty,a,b:= CALC a# * 2\nApple,3,2,\nPear,5,4,= SUM a1 a2\n").unwrap();
+
+        for format in [SpreadsheetFormat::Xlsx, SpreadsheetFormat::Ods] {
+            let (values, formulas) = export(&csv, format);
+            assert_eq!(values, vec!["Price|Qty|a|b:= CALC a# * 2", "Apple|3|2|4", "Pear|5|4|6"], "{:?}", format);
+
+            let expected = match format {
+                SpreadsheetFormat::Xlsx => vec!["D2: C2*2", "D3: SUM(C2:C3)"],
+                SpreadsheetFormat::Ods  => vec!["D2: of:=[.C2]*2", "D3: of:=SUM([.C2:.C3])"],
+            };
+            assert_eq!(formulas, expected, "{:?}", format);
+        }
+    }
+
+    /// Writes a sheet as a spreadsheet file in the temp directory and loads it back.
+    fn round_trip(csv: &CSV, format: SpreadsheetFormat, name: &str) -> (CSV, Vec<(String, String)>) {
+        let mut file = Vec::new();
+        write_spreadsheet(csv, format, &mut file).unwrap();
+
+        let path = std::env::temp_dir().join(format!("echcell_{}_{}", std::process::id(), name));
+        std::fs::write(&path, file).unwrap();
+        let loaded = CSV::from_spreadsheet(&path.display().to_string(), None).unwrap();
+        std::fs::remove_file(path).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn native_formulas_are_translated_back() {
+        let header: Vec<String> = ["A", "B", "x"].iter().map(|name| name.to_string()).collect();
+        let translate = |formula| from_native_formula(&header, formula);
+
+        assert_eq!(translate("=SUM(A2:A6)").as_deref(), Some("= SUM A1 A5"));
+        assert_eq!(translate("of:=AVERAGE([.$C$2:.C4])").as_deref(), Some("= AVG $x$1 x3"));
+        assert_eq!(translate("=C2*2-B2").as_deref(), Some("= CALC x1 * 2 - B1"));
+        assert_eq!(translate("=IF(A2>3,SUM(A2:A6),0)").as_deref(), Some("= IF A1 > 3 THEN SUM A1 A5 ELSE 0"));
+        // (The header row, columns without a name and other functions can't be expressed)
+        assert_eq!(translate("=A1*2"), None);
+        assert_eq!(translate("=D2*2"), None);
+        assert_eq!(translate("=VLOOKUP(A2,B2:C4,2)"), None);
+    }
+
+    #[test]
+    fn translated_formulas_are_read_back() {
+        let header: Vec<String> = ["A", "B", "x"].iter().map(|name| name.to_string()).collect();
+        let formulas = [
+            "= SUM A1 A5",
+            "= AVG $x$1 x3",
+            "= SUM A1 x1",
+            "= CALC x1 * 2 - B1 / 4",
+            "= CALC $A1 + B$2",
+            "= IF A1 > 3 THEN yes ELSE SUM A1 A2",
+            "= IF A1 <= x2 THEN CALC A1 * 2 ELSE 0",
+        ];
+
+        for format in [SpreadsheetFormat::Xlsx, SpreadsheetFormat::Ods] {
+            for formula in formulas {
+                let native = to_native_formula(&header, formula, format).unwrap();
+                assert_eq!(from_native_formula(&header, &native).as_deref(), Some(formula), "{:?}: {}", format, native);
+            }
+        }
+    }
+
+    #[test]
+    fn exported_sheets_are_read_back() {
+        let source = "Price,Qty,a,b:= CALC a# * 2\nApple,3,2,\nPear,5,4,= SUM a1 a2\n";
+        let csv = CSV::from_string("prices.csv", source).unwrap();
+
+        for (format, name) in [(SpreadsheetFormat::Xlsx, "prices.xlsx"), (SpreadsheetFormat::Ods, "prices.ods")] {
+            let (loaded, unsupported) = round_trip(&csv, format, name);
+            assert!(unsupported.is_empty(), "{:?}: {:?}", format, unsupported);
+            assert_eq!(loaded.get_source_header(), csv.get_source_header(), "{:?}", format);
+            assert_eq!(loaded.get_body(), csv.get_body(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn untranslated_formulas_are_reported() {
+        let csv = CSV::from_rows("lookup.csv", vec!["a".to_string(), "b".to_string()], vec![vec!["1".to_string(), "= CALC a1 * 2".to_string()]]);
+        let mut file = Vec::new();
+        write_spreadsheet(&csv, SpreadsheetFormat::Xlsx, &mut file).unwrap();
+
+        // Replacing the formula by one echcell can't express:
+        // (The exported formula "A2*2" is rewritten in the xml of the sheet)
+        let mut archive = zip::ZipArchive::new(Cursor::new(file)).unwrap();
+        let mut patched = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).unwrap();
+            let mut contents = Vec::new();
+            std::io::Read::read_to_end(&mut entry, &mut contents).unwrap();
+            let contents = String::from_utf8(contents).unwrap().replace("A2*2", "ROUND(A2,0)");
+            patched.start_file(entry.name(), zip::write::SimpleFileOptions::default()).unwrap();
+            std::io::Write::write_all(&mut patched, contents.as_bytes()).unwrap();
+        }
+
+        let path = std::env::temp_dir().join(format!("echcell_{}_lookup.xlsx", std::process::id()));
+        std::fs::write(&path, patched.finish().unwrap().into_inner()).unwrap();
+        let (loaded, unsupported) = CSV::from_spreadsheet(&path.display().to_string(), None).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(unsupported, vec![("B2".to_string(), "=ROUND(A2,0)".to_string())]);
+        assert_eq!(loaded.get_body(), vec![vec!["1".to_string(), "2".to_string()]]);
+    }
+}
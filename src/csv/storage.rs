@@ -0,0 +1,667 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Minimum amount of cells for a body to be stored sparsely.
+const SPARSE_MIN_CELLS: usize = 65_536;
+
+/// Amount of rows a cell can be set below the last row before the storage switches to sparse.
+const SPARSE_MIN_GAP: usize = 4096;
+
+/// TEXT INTERNER
+/// Stores every distinct text once; cells only hold the index of their text.
+/// The index and the lookup of a text share 1 allocation (the lookup only holds another reference to it).
+/// Every index counts the cells that hold it: texts that aren't held anymore are freed and their index is reused.
+#[derive(Debug, Default)]
+struct Interner {
+    texts: Vec<Option<Arc<str>>>,
+    counts: Vec<usize>,
+    ids: HashMap<Arc<str>, u32>,
+    free: Vec<u32>,
+}
+
+impl Interner {
+    /// Returns the index of a text, storing it if it's new.
+    /// Every call has to be matched by a `release` once the cell doesn't hold the text anymore.
+    fn intern(&mut self, text: &str) -> u32 {
+        if let Some(id) = self.ids.get(text) {
+            self.counts[*id as usize] += 1;
+            return *id;
+        }
+
+        let text: Arc<str> = Arc::from(text);
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.texts[id as usize] = Some(Arc::clone(&text));
+                self.counts[id as usize] = 1;
+                id
+            },
+            None => {
+                self.texts.push(Some(Arc::clone(&text)));
+                self.counts.push(1);
+                (self.texts.len() - 1) as u32
+            },
+        };
+        self.ids.insert(text, id);
+        id
+    }
+
+    /// Drops 1 use of an index; the text is freed when no cell holds it anymore.
+    fn release(&mut self, id: u32) {
+        let count = &mut self.counts[id as usize];
+        *count -= 1;
+        if *count == 0 {
+            if let Some(text) = self.texts[id as usize].take() {
+                self.ids.remove(&text);
+            }
+            self.free.push(id);
+        }
+    }
+
+    /// Returns the text of an index.
+    fn get(&self, id: u32) -> &str {
+        self.texts[id as usize].as_deref().unwrap_or_default()
+    }
+
+    /// Returns the amount of texts that are currently stored.
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.ids.len()
+    }
+}
+
+/// A cell of a mixed column or of the sparse storage.
+#[derive(Debug, Clone, Copy)]
+enum Item {
+    Empty,
+    Number(f64),
+    Text(u32),
+}
+
+/// COLUMN
+/// Cells of 1 column stored in a contiguous buffer.
+#[derive(Debug)]
+enum Column {
+    /// Every row has a number in this column (stored in its parsed form).
+    Numbers(Vec<f64>),
+    /// Numbers, interned texts (including expressions) and empty cells.
+    Mixed(Vec<Item>),
+}
+
+/// COLUMNAR CELL STORAGE
+/// Body of a CSV object stored column by column.
+/// Numbers are parsed once when they're stored, so reading them doesn't need `parse::<f64>()` again.
+/// Only numbers that are written in their canonical form ("35", "1.5") are stored as numbers,
+/// everything else ("007", "1e3", text, expressions) is interned, so every cell reads back exactly as it was written.
+#[derive(Debug, Default)]
+pub struct Columns {
+    columns: Vec<Column>,
+    widths: Vec<usize>,
+    texts: Interner,
+}
+
+impl Columns {
+    /// Creates the storage from rows of cells.
+    pub fn from_rows(rows: Vec<Vec<String>>) -> Self {
+        let mut storage = Self { widths: rows.iter().map(Vec::len).collect(), ..Self::default() };
+        let width = storage.widths.iter().copied().max().unwrap_or(0);
+
+        let columns = (0..width).map(|x| {
+            let numbers: Option<Vec<f64>> = rows.iter().map(|row| row.get(x).and_then(|item| parse_number(item))).collect();
+            match numbers {
+                Some(numbers) => Column::Numbers(numbers),
+                None => Column::Mixed(rows.iter().map(|row| make_item(&mut storage.texts, row.get(x).map(String::as_str).unwrap_or(""))).collect()),
+            }
+        }).collect();
+
+        storage.columns = columns;
+        storage
+    }
+
+    /// Returns the amount of rows.
+    pub fn len(&self) -> usize {
+        self.widths.len()
+    }
+
+    /// Returns the amount of cells of a row.
+    pub fn get_row_width(&self, y: usize) -> usize {
+        self.widths.get(y).copied().unwrap_or(0)
+    }
+
+    /// Returns the content of a cell (an empty String if the cell doesn't exist).
+    pub fn get(&self, x: usize, y: usize) -> String {
+        self.get_str(x, y).into_owned()
+    }
+
+    /// Returns the content of a cell like `get`; texts are borrowed from the storage, only numbers are formatted.
+    pub fn get_str(&self, x: usize, y: usize) -> Cow<'_, str> {
+        if x >= self.get_row_width(y) {
+            return Cow::Borrowed("");
+        }
+
+        match &self.columns[x] {
+            Column::Numbers(numbers) => Cow::Owned(numbers[y].to_string()),
+            Column::Mixed(items)     => match items[y] {
+                Item::Empty      => Cow::Borrowed(""),
+                Item::Number(n)  => Cow::Owned(n.to_string()),
+                Item::Text(id)   => Cow::Borrowed(self.texts.get(id)),
+            },
+        }
+    }
+
+    /// Returns the number stored in a cell (None if the cell doesn't hold a number).
+    pub fn get_number(&self, x: usize, y: usize) -> Option<f64> {
+        if x >= self.get_row_width(y) {
+            return None;
+        }
+
+        match &self.columns[x] {
+            Column::Numbers(numbers) => Some(numbers[y]),
+            Column::Mixed(items)     => match items[y] {
+                Item::Number(n) => Some(n),
+                _ => None,
+            },
+        }
+    }
+
+    /// Returns the numbers of the rows `y_start..=y_end` of a column as a slice.
+    /// Only works for columns that hold a number in every row (returns None otherwise).
+    pub fn get_numbers(&self, x: usize, y_start: usize, y_end: usize) -> Option<&[f64]> {
+        match self.columns.get(x) {
+            Some(Column::Numbers(numbers)) if y_start <= y_end && y_end < numbers.len() => Some(&numbers[y_start..=y_end]),
+            _ => None,
+        }
+    }
+
+    /// Overwrites a cell; missing rows and cells are added as empty cells.
+    pub fn set(&mut self, x: usize, y: usize, item: &str) {
+        if y >= self.len() {
+            self.resize(y + 1);
+        }
+        if x >= self.widths[y] {
+            self.widths[y] = x + 1;
+        }
+        while self.columns.len() <= x {
+            self.columns.push(Column::Mixed(vec![Item::Empty; self.len()]));
+        }
+
+        // Numeric columns stay numeric as long as only numbers are written into them:
+        if let (Column::Numbers(numbers), Some(n)) = (&mut self.columns[x], parse_number(item)) {
+            numbers[y] = n;
+            return;
+        }
+
+        let new_item = make_item(&mut self.texts, item);
+        let column = &mut self.columns[x];
+        if let Column::Numbers(numbers) = column {
+            *column = Column::Mixed(numbers.iter().map(|n| Item::Number(*n)).collect());
+        }
+        if let Column::Mixed(items) = column {
+            release_item(&mut self.texts, items[y]);
+            items[y] = new_item;
+        }
+    }
+
+    /// Changes the amount of rows; new rows are empty.
+    pub fn resize(&mut self, rows: usize) {
+        self.widths.resize(rows, 0);
+        self.columns.iter_mut().for_each(|column| {
+            // (Numeric columns can't hold the new empty cells)
+            if let Column::Numbers(numbers) = column {
+                if numbers.len() < rows {
+                    *column = Column::Mixed(numbers.iter().map(|n| Item::Number(*n)).collect());
+                }
+            }
+
+            match column {
+                Column::Numbers(numbers) => numbers.truncate(rows),
+                Column::Mixed(items)     => {
+                    items.iter().skip(rows).for_each(|item| release_item(&mut self.texts, *item));
+                    items.resize(rows, Item::Empty);
+                },
+            }
+        });
+    }
+
+    /// Returns the non-empty cells of the rows `y_start..=y_end` of a column.
+    pub fn get_column_cells(&self, x: usize, y_start: usize, y_end: usize) -> Vec<(usize, String)> {
+        (y_start..=y_end)
+            .map(|y| (y, self.get_str(x, y)))
+            .filter(|(_, item)| !item.is_empty())
+            .map(|(y, item)| (y, item.into_owned()))
+            .collect()
+    }
+
+    /// Returns the coordinates of every non-empty cell.
+    pub fn get_filled_cells(&self) -> Vec<(usize, usize)> {
+        (0..self.len())
+            .flat_map(|y| (0..self.get_row_width(y)).map(move |x| (x, y)))
+            .filter(|&(x, y)| !self.get_str(x, y).is_empty())
+            .collect()
+    }
+
+    /// Returns the cells of a row.
+    pub fn get_row(&self, y: usize) -> Vec<String> {
+        (0..self.get_row_width(y)).map(|x| self.get(x, y)).collect()
+    }
+
+    /// Changes the amount of cells of a row (the cells past the new width have to be empty).
+    fn set_row_width(&mut self, y: usize, width: usize) {
+        if y >= self.len() {
+            self.resize(y + 1);
+        }
+        self.widths[y] = width;
+    }
+}
+
+/// SPARSE CELL STORAGE
+/// Body of a CSV object that only stores its non-empty cells.
+/// Used for large grids with a few cells scattered around: memory depends on the amount of non-empty cells,
+/// and the cells of a column range are found without visiting the empty ones.
+#[derive(Debug, Default)]
+pub struct Sparse {
+    cells: BTreeMap<(usize, usize), Item>,
+    widths: BTreeMap<usize, usize>,
+    rows: usize,
+    texts: Interner,
+}
+
+impl Sparse {
+    /// Creates the storage from rows of cells.
+    pub fn from_rows(rows: Vec<Vec<String>>) -> Self {
+        let mut storage = Self { rows: rows.len(), ..Self::default() };
+
+        rows.iter().enumerate().for_each(|(y, row)| {
+            if !row.is_empty() {
+                storage.widths.insert(y, row.len());
+            }
+            row.iter().enumerate().filter(|(_, item)| !item.is_empty()).for_each(|(x, item)| {
+                let item = make_item(&mut storage.texts, item);
+                storage.cells.insert((x, y), item);
+            });
+        });
+
+        storage
+    }
+
+    /// Returns the amount of rows.
+    pub fn len(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns the amount of cells of a row.
+    pub fn get_row_width(&self, y: usize) -> usize {
+        self.widths.get(&y).copied().unwrap_or(0)
+    }
+
+    /// Returns the content of a cell (an empty String if the cell is empty or doesn't exist).
+    pub fn get(&self, x: usize, y: usize) -> String {
+        self.get_str(x, y).into_owned()
+    }
+
+    /// Returns the content of a cell like `get`; texts are borrowed from the storage, only numbers are formatted.
+    pub fn get_str(&self, x: usize, y: usize) -> Cow<'_, str> {
+        match self.cells.get(&(x, y)) {
+            Some(Item::Number(n)) => Cow::Owned(n.to_string()),
+            Some(Item::Text(id))  => Cow::Borrowed(self.texts.get(*id)),
+            _ => Cow::Borrowed(""),
+        }
+    }
+
+    /// Returns the number stored in a cell (None if the cell doesn't hold a number).
+    pub fn get_number(&self, x: usize, y: usize) -> Option<f64> {
+        match self.cells.get(&(x, y)) {
+            Some(Item::Number(n)) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Overwrites a cell; missing rows and cells are added as empty cells.
+    pub fn set(&mut self, x: usize, y: usize, item: &str) {
+        self.rows = self.rows.max(y + 1);
+        if x >= self.get_row_width(y) {
+            self.widths.insert(y, x + 1);
+        }
+
+        let old = if item.is_empty() {
+            self.cells.remove(&(x, y))
+        }
+        else {
+            let item = make_item(&mut self.texts, item);
+            self.cells.insert((x, y), item)
+        };
+        if let Some(old) = old {
+            release_item(&mut self.texts, old);
+        }
+    }
+
+    /// Changes the amount of rows; new rows are empty.
+    pub fn resize(&mut self, rows: usize) {
+        if rows < self.rows {
+            self.cells.retain(|(_, y), item| {
+                if *y < rows {
+                    return true;
+                }
+                release_item(&mut self.texts, *item);
+                false
+            });
+            self.widths.split_off(&rows);
+        }
+        self.rows = rows;
+    }
+
+    /// Returns the non-empty cells of the rows `y_start..=y_end` of a column.
+    pub fn get_column_cells(&self, x: usize, y_start: usize, y_end: usize) -> Vec<(usize, String)> {
+        if y_start > y_end {
+            return Vec::new();
+        }
+
+        self.cells.range((x, y_start)..=(x, y_end))
+            .map(|((_, y), _)| (*y, self.get(x, *y)))
+            .collect()
+    }
+
+    /// Returns the coordinates of every non-empty cell.
+    pub fn get_filled_cells(&self) -> Vec<(usize, usize)> {
+        self.cells.keys().copied().collect()
+    }
+
+    /// Returns the cells of a row.
+    pub fn get_row(&self, y: usize) -> Vec<String> {
+        (0..self.get_row_width(y)).map(|x| self.get(x, y)).collect()
+    }
+
+    /// Changes the amount of cells of a row (the cells past the new width have to be empty).
+    fn set_row_width(&mut self, y: usize, width: usize) {
+        self.rows = self.rows.max(y + 1);
+        if width == 0 {
+            self.widths.remove(&y);
+        }
+        else {
+            self.widths.insert(y, width);
+        }
+    }
+}
+
+/// CELL STORAGE
+/// Body of a CSV object: columnar for dense tables, sparse for large and mostly empty grids.
+/// The storage is picked when the body is loaded; setting a cell far below the last row switches a columnar body to sparse.
+#[derive(Debug)]
+pub enum Storage {
+    Columnar(Columns),
+    Sparse(Sparse),
+}
+
+impl Storage {
+    /// Creates the storage from rows of cells.
+    /// Bodies with at least `SPARSE_MIN_CELLS` cells of which less than 10% are filled are stored sparsely.
+    pub fn from_rows(rows: Vec<Vec<String>>) -> Self {
+        let cells: usize = rows.iter().map(Vec::len).sum();
+        let filled = rows.iter().flatten().filter(|item| !item.is_empty()).count();
+
+        if cells >= SPARSE_MIN_CELLS && filled * 10 < cells {
+            Self::Sparse(Sparse::from_rows(rows))
+        }
+        else {
+            Self::Columnar(Columns::from_rows(rows))
+        }
+    }
+
+    /// Returns whether the cells are stored sparsely.
+    pub fn is_sparse(&self) -> bool {
+        matches!(self, Self::Sparse(_))
+    }
+
+    /// Returns the amount of rows.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Columnar(storage) => storage.len(),
+            Self::Sparse(storage)   => storage.len(),
+        }
+    }
+
+    /// Returns the amount of cells of a row.
+    pub fn get_row_width(&self, y: usize) -> usize {
+        match self {
+            Self::Columnar(storage) => storage.get_row_width(y),
+            Self::Sparse(storage)   => storage.get_row_width(y),
+        }
+    }
+
+    /// Returns the content of a cell (an empty String if the cell doesn't exist).
+    pub fn get(&self, x: usize, y: usize) -> String {
+        self.get_str(x, y).into_owned()
+    }
+
+    /// Returns the content of a cell without copying texts (see `get`).
+    pub fn get_str(&self, x: usize, y: usize) -> Cow<'_, str> {
+        match self {
+            Self::Columnar(storage) => storage.get_str(x, y),
+            Self::Sparse(storage)   => storage.get_str(x, y),
+        }
+    }
+
+    /// Returns the number stored in a cell (None if the cell doesn't hold a number).
+    pub fn get_number(&self, x: usize, y: usize) -> Option<f64> {
+        match self {
+            Self::Columnar(storage) => storage.get_number(x, y),
+            Self::Sparse(storage)   => storage.get_number(x, y),
+        }
+    }
+
+    /// Returns the numbers of the rows `y_start..=y_end` of a column as a slice (columnar storage only).
+    pub fn get_numbers(&self, x: usize, y_start: usize, y_end: usize) -> Option<&[f64]> {
+        match self {
+            Self::Columnar(storage) => storage.get_numbers(x, y_start, y_end),
+            Self::Sparse(_)         => None,
+        }
+    }
+
+    /// Returns the non-empty cells of the rows `y_start..=y_end` of a column.
+    pub fn get_column_cells(&self, x: usize, y_start: usize, y_end: usize) -> Vec<(usize, String)> {
+        match self {
+            Self::Columnar(storage) => storage.get_column_cells(x, y_start, y_end),
+            Self::Sparse(storage)   => storage.get_column_cells(x, y_start, y_end),
+        }
+    }
+
+    /// Returns the coordinates of every non-empty cell.
+    pub fn get_filled_cells(&self) -> Vec<(usize, usize)> {
+        match self {
+            Self::Columnar(storage) => storage.get_filled_cells(),
+            Self::Sparse(storage)   => storage.get_filled_cells(),
+        }
+    }
+
+    /// Overwrites a cell; missing rows and cells are added as empty cells.
+    pub fn set(&mut self, x: usize, y: usize, item: &str) {
+        self.make_room(y + 1);
+        match self {
+            Self::Columnar(storage) => storage.set(x, y, item),
+            Self::Sparse(storage)   => storage.set(x, y, item),
+        }
+    }
+
+    /// Changes the amount of rows; new rows are empty.
+    pub fn resize(&mut self, rows: usize) {
+        self.make_room(rows);
+        match self {
+            Self::Columnar(storage) => storage.resize(rows),
+            Self::Sparse(storage)   => storage.resize(rows),
+        }
+    }
+
+    /// Returns the cells of a row.
+    pub fn get_row(&self, y: usize) -> Vec<String> {
+        match self {
+            Self::Columnar(storage) => storage.get_row(y),
+            Self::Sparse(storage)   => storage.get_row(y),
+        }
+    }
+
+    /// Replaces every cell of a row; the row gets the width of the new cells.
+    pub fn set_row(&mut self, y: usize, row: &[String]) {
+        // (Cells of the old row that aren't overwritten are emptied)
+        let width = self.get_row_width(y).max(row.len());
+        (0..width).for_each(|x| self.set(x, y, row.get(x).map_or("", String::as_str)));
+
+        match self {
+            Self::Columnar(storage) => storage.set_row_width(y, row.len()),
+            Self::Sparse(storage)   => storage.set_row_width(y, row.len()),
+        }
+    }
+
+    /// Switches a columnar body to sparse storage if it would have to grow by a big gap of empty rows.
+    fn make_room(&mut self, rows: usize) {
+        if let Self::Columnar(storage) = self {
+            if rows > storage.len() + SPARSE_MIN_GAP {
+                let rows = (0..storage.len()).map(|y| storage.get_row(y)).collect();
+                *self = Self::Sparse(Sparse::from_rows(rows));
+            }
+        }
+    }
+}
+
+/// Converts the content of a cell to an item (texts are interned).
+fn make_item(texts: &mut Interner, item: &str) -> Item {
+    if item.is_empty() {
+        Item::Empty
+    }
+    else if let Some(n) = parse_number(item) {
+        Item::Number(n)
+    }
+    else {
+        Item::Text(texts.intern(item))
+    }
+}
+
+/// Drops the text of an item that is removed from the storage (see `Interner::release`).
+fn release_item(texts: &mut Interner, item: Item) {
+    if let Item::Text(id) = item {
+        texts.release(id);
+    }
+}
+
+/// Parses a number that is written in its canonical form (it reads back the same way it was written).
+fn parse_number(item: &str) -> Option<f64> {
+    match item.parse::<f64>() {
+        Ok(n) if n.to_string() == item => Some(n),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overwritten_texts_are_freed() {
+        let mut storage = Columns::from_rows(vec![vec!["a".to_string(), "b".to_string()]]);
+        (0..100).for_each(|i| storage.set(0, 0, &format!("text {}", i)));
+        assert_eq!(storage.texts.len(), 2);
+        assert_eq!(storage.texts.texts.len(), 3);
+        assert_eq!(storage.get(0, 0), "text 99");
+
+        // Texts held by other cells stay:
+        storage.set(0, 0, "b");
+        storage.set(0, 0, "1");
+        assert_eq!(storage.texts.len(), 1);
+        assert_eq!(storage.get(1, 0), "b");
+
+        storage.resize(0);
+        assert_eq!(storage.texts.len(), 0);
+    }
+
+    #[test]
+    fn texts_are_stored_once_and_read_without_copies() {
+        let mut storage = Storage::from_rows(vec![vec!["a".to_string(), "1".to_string()], vec!["a".to_string(), "b".to_string()]]);
+        let Storage::Columnar(columns) = &storage else { panic!("small bodies are columnar") };
+        // (The slot and the lookup hold the same allocation)
+        let text = columns.texts.texts[0].as_ref().unwrap();
+        assert_eq!(Arc::strong_count(text), 2);
+        assert!(Arc::ptr_eq(text, columns.texts.ids.get_key_value("a").unwrap().0));
+
+        assert!(matches!(storage.get_str(0, 1), Cow::Borrowed("a")));
+        assert!(matches!(storage.get_str(1, 0), Cow::Owned(_)));
+        assert_eq!(storage.get_str(5, 5), "");
+
+        storage.set(0, 9000, "c");
+        assert!(storage.is_sparse());
+        assert!(matches!(storage.get_str(0, 9000), Cow::Borrowed("c")));
+    }
+
+    #[test]
+    fn removed_sparse_cells_free_their_texts() {
+        let mut storage = Sparse::from_rows(vec![vec!["a".to_string()], vec!["a".to_string()]]);
+        storage.set(0, 0, "");
+        assert_eq!(storage.texts.len(), 1);
+        (0..100).for_each(|i| storage.set(0, 1, &format!("text {}", i)));
+        assert_eq!(storage.texts.len(), 1);
+
+        storage.resize(1);
+        assert_eq!(storage.texts.len(), 0);
+    }
+
+    #[test]
+    fn rows_are_replaced_with_their_width() {
+        let mut storage = Storage::from_rows(vec![vec!["1".to_string(), "x".to_string(), "y".to_string()]]);
+        storage.set_row(0, &["2".to_string()]);
+        assert_eq!(storage.get_row(0), vec!["2"]);
+
+        storage.set(2, 0, "z");
+        assert_eq!(storage.get_row(0), vec!["2", "", "z"]);
+    }
+
+    #[test]
+    fn empty_grids_are_stored_sparsely() {
+        let mut rows = vec![vec![String::new(); 256]; 256];
+        rows[3][200] = "x".to_string();
+        rows[255][0] = "1".to_string();
+
+        let storage = Storage::from_rows(rows.clone());
+        assert!(storage.is_sparse());
+        assert_eq!(storage.get_filled_cells().len(), 2);
+        assert_eq!((0..rows.len()).map(|y| storage.get_row(y)).collect::<Vec<_>>(), rows);
+        assert_eq!(storage.get_number(0, 255), Some(1.0));
+
+        // (Small or filled bodies stay columnar)
+        assert!(!Storage::from_rows(vec![vec![String::new(); 10]; 10]).is_sparse());
+        assert!(!Storage::from_rows(vec![vec!["1".to_string(); 256]; 256]).is_sparse());
+    }
+
+    #[test]
+    fn cells_far_below_the_last_row_switch_to_sparse() {
+        let mut storage = Storage::from_rows(vec![vec!["a".to_string(), "1".to_string()]]);
+        storage.set(0, 100, "b");
+        assert!(!storage.is_sparse());
+
+        storage.set(1, 100 + SPARSE_MIN_GAP + 1, "2");
+        assert!(storage.is_sparse());
+        assert_eq!(storage.len(), SPARSE_MIN_GAP + 102);
+        assert_eq!(storage.get_row(0), vec!["a", "1"]);
+        assert_eq!(storage.get(0, 100), "b");
+        assert_eq!(storage.get_column_cells(1, 0, storage.len() - 1), vec![(0, "1".to_string()), (SPARSE_MIN_GAP + 101, "2".to_string())]);
+    }
+
+    #[test]
+    fn storage_switches_at_the_sparse_limits() {
+        // (A row of the given amount of cells of which the first ones are filled)
+        let row = |cells: usize, filled: usize| (0..cells).map(|x| if x < filled { "x".to_string() } else { String::new() }).collect::<Vec<_>>();
+
+        // Amount of cells:
+        assert!(!Storage::from_rows(vec![row(SPARSE_MIN_CELLS - 1, 0)]).is_sparse());
+        assert!(Storage::from_rows(vec![row(SPARSE_MIN_CELLS, 0)]).is_sparse());
+
+        // Filled share (sparse below 10%):
+        assert!(!Storage::from_rows(vec![row(SPARSE_MIN_CELLS, SPARSE_MIN_CELLS / 10 + 1)]).is_sparse());
+        assert!(Storage::from_rows(vec![row(SPARSE_MIN_CELLS, SPARSE_MIN_CELLS / 10)]).is_sparse());
+
+        // Gap below the last row:
+        let mut storage = Storage::from_rows(vec![vec!["a".to_string()]]);
+        storage.set(0, SPARSE_MIN_GAP, "b");
+        assert!(!storage.is_sparse());
+        storage.resize(storage.len() + SPARSE_MIN_GAP + 1);
+        assert!(storage.is_sparse());
+        assert_eq!((storage.get(0, 0), storage.get(0, SPARSE_MIN_GAP)), ("a".to_string(), "b".to_string()));
+    }
+}
@@ -0,0 +1,316 @@
+use std::fs;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use crate::csv::{CSV, get_output_path};
+use super::atomic::{Overwrite, write_atomic};
+use super::format::{OutputWriter, CsvWriter};
+use crate::error::CsvError;
+use super::graph::{DepGraph, Recalc, get_references};
+use super::refs::shift_word;
+
+/// Largest amount of previous rows a formula may reach for the file to be streamed.
+pub const MAX_STREAM_WINDOW: usize = 1024;
+
+/// Checks whether a csv file can be evaluated by streaming its rows (see `generate_output_streaming`).
+/// Reads the file line by line without keeping the rows.
+/// Returns Some(amount of previous rows the formulas reach) or None if a formula reaches a later row
+/// or more than `MAX_STREAM_WINDOW` rows back (these files have to be loaded fully).
+pub fn get_stream_window(file_path: &str) -> Result<Option<usize>, CsvError> {
+    let mut lines = open_lines(file_path)?;
+    let sheet = match lines.next() {
+        Some(header) => CSV::from_rows(file_path, split_line(&read_line(header)?), Vec::new()),
+        None         => return Err(CsvError::FileError("Csv file has no header...".to_string())),
+    };
+
+    let mut window = 0;
+    for (y, line) in lines.enumerate() {
+        let row = split_line(&read_line(line)?);
+        let width = row.len().max(sheet.column_formulas.len());
+
+        for x in 0..width {
+            let item = match (row.get(x), sheet.column_formulas.get(x)) {
+                (Some(item), _) if !item.is_empty() => item.clone(),
+                (_, Some(Some(formula)))            => formula.replace('#', &(y + 1).to_string()),
+                _ => continue,
+            };
+
+            for (_, y_ref) in get_references(&item, &sheet) {
+                // Later rows aren't read yet when the row is evaluated:
+                if y_ref > y || y - y_ref > MAX_STREAM_WINDOW {
+                    return Ok(None);
+                }
+                window = window.max(y - y_ref);
+            }
+        }
+    }
+
+    Ok(Some(window))
+}
+
+/// Evaluates a csv file row by row and writes the output file while reading it.
+/// Only the given amount of previous (already evaluated) rows is kept in memory,
+/// so every formula may only reach its own row and at most `window` rows back (see `get_stream_window`).
+/// The output is the same as the one of `generate_output`.
+pub fn generate_output_streaming(file_path: &str, window: usize) -> Result<(), CsvError> {
+    write_atomic(Path::new(&get_output_path(file_path)), Overwrite::Replace, |output_file| write_output_streaming(file_path, window, output_file))
+}
+
+/// Same as `generate_output_streaming` but writes the evaluated rows to a writer.
+pub fn write_output_streaming(file_path: &str, window: usize, output_file: &mut impl Write) -> Result<(), CsvError> {
+    write_output_streaming_as(file_path, window, output_file, &mut CsvWriter)
+}
+
+/// Same as `write_output_streaming` but writes the rows with the given output writer (see `OutputFormat`).
+/// (A `TableWriter` keeps every row until the end, so streaming into a table doesn't save any memory.)
+pub fn write_output_streaming_as(file_path: &str, window: usize, output_file: &mut impl Write, output_writer: &mut dyn OutputWriter) -> Result<(), CsvError> {
+    let mut lines = open_lines(file_path)?;
+    let raw_header = match lines.next() {
+        Some(header) => split_line(&read_line(header)?),
+        None         => return Err(CsvError::FileError("Csv file has no header...".to_string())),
+    };
+
+    // The sheet only holds the window as a ring buffer of `window + 1` rows that is stored twice
+    // (row y is kept in the slots `y % size` and `y % size + size`), so the current row and the rows it reaches
+    // are always consecutive slots that end with the slot of the current row.
+    let size = window + 1;
+    let mut sheet = CSV::from_rows(file_path, raw_header, vec![Vec::new(); 2 * size]);
+    sheet.calc = Some(Recalc { graph: DepGraph::default(), values: HashMap::new(), threads: 1 });
+
+    output_writer.write_header(output_file, &sheet.header)?;
+
+    for (y, line) in lines.enumerate() {
+        let slot = y % size + size;
+
+        // Expanding the column formulas for the real row (in the slot, `#` would be the row of the slot):
+        let mut row = split_line(&read_line(line)?);
+        let width = row.len().max(sheet.column_formulas.iter().rposition(Option::is_some).map_or(0, |x| x + 1));
+        row.resize(width, String::new());
+        row.iter_mut().zip(sheet.column_formulas.iter()).for_each(|(item, formula)| {
+            if let (true, Some(formula)) = (item.is_empty(), formula) {
+                *item = formula.replace('#', &(y + 1).to_string());
+            }
+        });
+
+        // Moving the references of the row into its slot:
+        // (`$` markers are dropped because absolute rows have to move too)
+        let d_row = slot as isize - y as isize;
+        let row = row.iter()
+            .map(|item| {
+                if item.starts_with('=') {
+                    shift_row_refs(item, &sheet.header, d_row)
+                }
+                else {
+                    Ok(item.clone())
+                }
+            })
+            .collect::<Result<Vec<String>, CsvError>>()?;
+
+        sheet.set_window_row([slot - size, slot], y, &row);
+        output_writer.write_row(output_file, &sheet.get_output_row(slot))?;
+    }
+
+    output_writer.finish(output_file)
+}
+
+impl CSV {
+    /// Replaces a row of the streaming window (stored in both of its slots) and evaluates its expressions.
+    /// The results (errors included) are cached, so the formulas of later rows read them without evaluating them again.
+    /// `y` is the row of the file, it replaces the row of the slot in error messages.
+    fn set_window_row(&mut self, slots: [usize; 2], y: usize, row: &[String]) {
+        slots.iter().for_each(|&slot| {
+            let width = self.get_row_width(slot);
+            if let Some(calc) = &mut self.calc {
+                (0..width).for_each(|x| { calc.values.remove(&(x, slot)); });
+            }
+            self.body.set_row(slot, row);
+        });
+
+        // (Cycle errors are cached once the row is done: like in the full evaluation, every cell of a cycle finds the cycle itself)
+        let mut cycles = Vec::new();
+        for x in 0..self.get_row_width(slots[1]) {
+            if !self.get_cell_source(x, slots[1]).starts_with('=') {
+                continue;
+            }
+
+            match self.get_evaluated_value(x, slots[1]).map_err(|err| self.get_window_error(err, slots[1], y)) {
+                Err(err @ CsvError::CycleError(_)) => cycles.push((x, Err(err))),
+                value => self.cache_window_value(slots, x, value),
+            }
+        }
+        cycles.into_iter().for_each(|(x, value)| self.cache_window_value(slots, x, value));
+    }
+
+    /// Caches the value of a cell of the window in both slots of its row.
+    fn cache_window_value(&mut self, slots: [usize; 2], x: usize, value: Result<String, CsvError>) {
+        if let Some(calc) = &mut self.calc {
+            calc.values.insert((x, slots[0]), value.clone());
+            calc.values.insert((x, slots[1]), value);
+        }
+    }
+
+    /// Returns an error of a cell of the window with the cell pointers of the slot replaced by the ones of the file.
+    /// (Only cycles name a cell; every cell of a cycle is in the current row, since formulas never reach later rows)
+    fn get_window_error(&self, err: CsvError, slot: usize, y: usize) -> CsvError {
+        match err {
+            CsvError::CycleError(msg) => {
+                let msg = self.header.iter().fold(msg, |msg, col| {
+                    msg.replace(&format!("!{}{}...", col, slot + 1), &format!("!{}{}...", col, y + 1))
+                });
+                CsvError::CycleError(msg)
+            },
+            err => err,
+        }
+    }
+}
+
+/// Opens a csv file and returns an iterator over its lines.
+fn open_lines(file_path: &str) -> Result<io::Lines<BufReader<fs::File>>, CsvError> {
+    match fs::File::open(file_path) {
+        Ok(f)  => Ok(BufReader::new(f).lines()),
+        Err(_) => Err(CsvError::FileError("Could not read csv file...".to_string())),
+    }
+}
+
+/// Returns a line read from a csv file or a CsvError::FileError() if reading failed.
+fn read_line(line: io::Result<String>) -> Result<String, CsvError> {
+    match line {
+        Ok(line) => Ok(line),
+        Err(_)   => Err(CsvError::FileError("Could not read csv file...".to_string())),
+    }
+}
+
+/// Splits a line of a csv file by commas.
+fn split_line(line: &str) -> Vec<String> {
+    line.split(',').map(String::from).collect()
+}
+
+/// Moves every row reference of the same sheet by the given offset.
+/// Linked files and the words right after THEN and ELSE are left alone (IF returns those words as they are).
+fn shift_row_refs(item: &str, header: &[String], d_row: isize) -> Result<String, CsvError> {
+    let mut previous = "";
+    let words = item.split(' ')
+        .map(|word| {
+            let is_branch = previous == "THEN" || previous == "ELSE";
+            previous = word;
+            if word.contains('!') || is_branch {
+                Ok(word.to_string())
+            }
+            else {
+                shift_word(&word.replace('$', ""), header, 0, d_row)
+            }
+        })
+        .collect::<Result<Vec<String>, CsvError>>()?;
+
+    Ok(words.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv::{OutputFormat, write_output, write_output_as};
+
+    /// Writes a csv file into the temp directory and returns its path.
+    fn write_file(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("echcell_{}_{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    /// Returns the output of `write_output` and the streamed output of a file.
+    fn get_outputs(path: &str) -> (String, String) {
+        let mut output = Vec::new();
+        write_output(&CSV::new(path.to_string()).unwrap(), &mut output).unwrap();
+
+        let window = get_stream_window(path).unwrap().unwrap();
+        let mut streamed = Vec::new();
+        write_output_streaming(path, window, &mut streamed).unwrap();
+
+        (String::from_utf8(output).unwrap(), String::from_utf8(streamed).unwrap())
+    }
+
+    #[test]
+    fn streaming_matches_the_full_output() {
+        let mut contents = String::from("A,B,C:= CALC A# * 2\n");
+        (1..=20).for_each(|y| {
+            let b = if y < 3 { "0".to_string() } else { format!("= SUM A{} A{}", y - 2, y) };
+            contents.push_str(&format!("{},{},\n", y, b));
+        });
+        let path = write_file("stream.csv", &contents);
+
+        assert_eq!(get_stream_window(&path).unwrap(), Some(2));
+        let (output, streamed) = get_outputs(&path);
+        assert_eq!(streamed, output);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn streaming_keeps_errors_of_previous_rows() {
+        let path = write_file("stream_errors.csv", "A,B,C\nx,= CALC A1 + 1,\n2,= CALC B1 * 2,= SUM B1 B2\n3,= CALC B2 + A3,= IF B2 = B1 THEN same ELSE different\n");
+
+        let (output, streamed) = get_outputs(&path);
+        assert_eq!(streamed, output);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn later_rows_are_not_streamed() {
+        let path = write_file("stream_later.csv", "A,B\n1,= CALC A2 + 1\n2,3\n");
+        assert_eq!(get_stream_window(&path).unwrap(), None);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn streaming_matches_the_full_output_of_column_formulas_branches_and_cycles() {
+        let cases = [
+            ("stream_column.csv", "A,B:= SUM A1 A#\n1,\n2,\n3,\n"),
+            ("stream_absolute.csv", "A,B:= CALC A# * $A$1,C\n2,,x\n3,,= CALC B2 + B1\n4\n"),
+            ("stream_branch.csv", "A,B\n1,= IF A1 > 0 THEN A1 ELSE no\n-1,= IF A2 > 0 THEN CALC A1 ELSE A2\n"),
+            ("stream_cycle.csv", "A,B,C\n1,= CALC B1 + 1,= CALC A1 + B1\n2,= CALC C2 + 1,= CALC B2 * 2\n3,= CALC A3,= CALC C2 + 1\n"),
+        ];
+
+        for (name, contents) in cases {
+            let path = write_file(name, contents);
+            let (output, streamed) = get_outputs(&path);
+            assert_eq!(streamed, output, "{}", name);
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn streaming_matches_the_full_output_in_every_streamed_format() {
+        let path = write_file("stream_formats.csv", "A,B:= CALC A# * 2,C\n1,,<b>\n2,,= SUM B1 B2\nx,,= IF A2 > 1 THEN B9999 ELSE no\n");
+        // (The branch is returned as text, so it doesn't widen the window)
+        let window = get_stream_window(&path).unwrap().unwrap();
+        assert_eq!(window, 1);
+
+        let csv = CSV::new(path.clone()).unwrap();
+        for format in [OutputFormat::Csv, OutputFormat::Json, OutputFormat::Markdown, OutputFormat::Html] {
+            let mut output = Vec::new();
+            write_output_as(&csv, &mut output, format.get_writer().as_mut()).unwrap();
+            let mut streamed = Vec::new();
+            write_output_streaming_as(&path, window, &mut streamed, format.get_writer().as_mut()).unwrap();
+            assert_eq!(String::from_utf8(streamed).unwrap(), String::from_utf8(output).unwrap(), "{:?}", format);
+        }
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn windows_are_limited_to_the_max_stream_window() {
+        let mut contents = String::from("A,B\n");
+        (1..=MAX_STREAM_WINDOW + 2).for_each(|y| contents.push_str(&format!("{},\n", y)));
+
+        // (The last row reads exactly `MAX_STREAM_WINDOW` rows back, then 1 row further)
+        let last = MAX_STREAM_WINDOW + 3;
+        for (first, window) in [(last - MAX_STREAM_WINDOW, Some(MAX_STREAM_WINDOW)), (last - MAX_STREAM_WINDOW - 1, None)] {
+            let contents = format!("{}{},= SUM A{} A{}\n", contents, last, first, last);
+            let path = write_file("stream_max.csv", &contents);
+            assert_eq!(get_stream_window(&path).unwrap(), window);
+            if window.is_some() {
+                let (output, streamed) = get_outputs(&path);
+                assert_eq!(streamed, output);
+            }
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::fmt;
+
+/// VALUE
+/// Typed value of an evaluated cell.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+    Empty,
+}
+
+impl Value {
+    /// Creates a Value from the text of an evaluated cell.
+    /// Items that are parsable to f64 are Numbers, empty items are Empty and everything else is Text.
+    pub fn from_item(item: &str) -> Self {
+        if item.is_empty() {
+            Self::Empty
+        }
+        else if let Ok(n) = item.parse::<f64>() {
+            Self::Number(n)
+        }
+        else {
+            Self::Text(item.to_string())
+        }
+    }
+
+    /// Returns the number inside the value (None if it's not a Number).
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Self::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(n) => write!(f, "{}", n),
+            Self::Text(text) => write!(f, "{}", text),
+            Self::Empty      => write!(f, ""),
+        }
+    }
+}
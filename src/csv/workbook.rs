@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::csv::{CSV, generate_output};
+use crate::csv::links::Links;
+use crate::error::CsvError;
+
+/// WORKBOOK
+/// A set of named CSV sheets that are evaluated together.
+/// Every sheet can reach the others by name (`rates!B2`) or by file name (`rates.csv!B2`).
+/// The sheets share their links, but there is no graph across sheets: a linked cell is evaluated in its own sheet
+/// when a formula reads it (circular references through other sheets are still detected).
+#[derive(Debug)]
+pub struct Workbook {
+    pub sheets: Vec<(String, Arc<CSV>)>,
+    links: Arc<Links>,
+}
+
+impl Workbook {
+    /// Creates a workbook from every csv file of a directory.
+    /// Sheets are named after their file names without the extension and are ordered by name.
+    /// Output files (`out_*.csv`) are skipped.
+    pub fn open_dir(dir: &str) -> Result<Self, CsvError> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_)      => return Err(CsvError::FileError("Could not read workbook directory...".to_string())),
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "csv"))
+            .filter(|path| !path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("out_")))
+            .collect();
+        paths.sort();
+
+        let sheets = paths.into_iter().map(|path| (get_sheet_name(&path), path)).collect();
+        Self::from_paths(sheets)
+    }
+
+    /// Creates a workbook from a manifest file.
+    /// Every line of the manifest lists 1 sheet, either as `path` or as `name = path`.
+    /// Paths are relative to the manifest; empty lines and lines starting with '#' are skipped.
+    pub fn open_manifest(manifest: &str) -> Result<Self, CsvError> {
+        let contents = match fs::read_to_string(manifest) {
+            Ok(contents) => contents,
+            Err(_)       => return Err(CsvError::FileError("Could not read workbook manifest...".to_string())),
+        };
+        let dir = Path::new(manifest).parent().unwrap_or(Path::new(""));
+
+        let sheets = contents.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| match line.split_once('=') {
+                Some((name, path)) => (name.trim().to_string(), dir.join(path.trim())),
+                None               => (get_sheet_name(Path::new(line)), dir.join(line)),
+            })
+            .collect();
+
+        Self::from_paths(sheets)
+    }
+
+    /// Loads every (name, path) pair into one set of links.
+    fn from_paths(sheets: Vec<(String, PathBuf)>) -> Result<Self, CsvError> {
+        if sheets.is_empty() {
+            return Err(CsvError::FileError("Workbook has no sheets...".to_string()));
+        }
+
+        let links = Arc::new(Links::default());
+        let sheets = sheets.into_iter()
+            .map(|(name, path)| Ok((name.clone(), Links::register(&links, &name, &path)?)))
+            .collect::<Result<Vec<_>, CsvError>>()?;
+
+        Ok(Self { sheets, links })
+    }
+
+    /// Returns the sheet with the given name.
+    pub fn get_sheet(&self, name: &str) -> Option<Arc<CSV>> {
+        self.links.get_named(name)
+    }
+}
+
+/// Evaluates every sheet of a workbook and creates an output csv file for each of them.
+/// Returns a Result type of () or the CsvError of the first sheet whose output couldn't be created.
+pub fn generate_outputs(workbook: &Workbook) -> Result<(), CsvError> {
+    workbook.sheets.iter().try_for_each(|(_, sheet)| generate_output(sheet))
+}
+
+/// Returns the name of a sheet: its file name without the extension.
+fn get_sheet_name(path: &Path) -> String {
+    path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a directory in the temp directory with the given csv files.
+    fn get_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("echcell_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        files.iter().for_each(|(file, contents)| fs::write(dir.join(file), contents).unwrap());
+        dir
+    }
+
+    #[test]
+    fn sheets_reach_each_other_by_name() {
+        let dir = get_dir("workbook", &[
+            ("rates.csv", "A,B\nusd,2\n"),
+            ("sales.csv", "A,B\n3,= CALC A1 * rates!B1\n"),
+            ("out_sales.csv", "A,B\n3,6\n"),
+        ]);
+
+        let workbook = Workbook::open_dir(&dir.display().to_string()).unwrap();
+        let names: Vec<&str> = workbook.sheets.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["rates", "sales"]);
+        assert_eq!(workbook.get_sheet("sales").unwrap().get_cell_value("B1").unwrap(), "6");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn manifests_name_their_sheets() {
+        let dir = get_dir("manifest", &[
+            ("rates.csv", "A,B\nusd,2\n"),
+            ("sales.csv", "A,B\n3,= CALC A1 * fx!B1\n"),
+            ("book.txt", "# sheets\nfx = rates.csv\n\nsales.csv\n"),
+        ]);
+
+        let workbook = Workbook::open_manifest(&dir.join("book.txt").display().to_string()).unwrap();
+        assert_eq!(workbook.get_sheet("sales").unwrap().get_cell_value("B1").unwrap(), "6");
+        assert!(workbook.get_sheet("rates").is_none());
+
+        fs::write(dir.join("book.txt"), "rates.csv\nrates = sales.csv\n").unwrap();
+        assert!(matches!(Workbook::open_manifest(&dir.join("book.txt").display().to_string()), Err(CsvError::FileError(_))));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,14 +1,82 @@
 use std::fmt;
 use std::error;
+use crate::csv::Diagnostic;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum CsvError {
     FileError(String),
     ArgError,
     ExprError(String),
     RangeError(String),
     CellPError(String),
+    CycleError(String),
+    TokenError,
     RunError(String),
+    // Every failing cell (pointer and error) of a sheet evaluated in strict mode:
+    StrictError(Vec<(String, CsvError)>),
+    // The error of a cell together with its location (cell pointer, formula and the word that caused it):
+    CellError(Box<Diagnostic>),
+    // Amount of problems `check` found in the sheets (the problems themselves are printed as diagnostics):
+    CheckError(usize),
+    // An error whose cells were already printed as diagnostics (only its first line is shown):
+    ReportedError(Box<CsvError>),
+}
+
+impl CsvError {
+    /// Returns the name of the variant ("ExprError", "CellPError"...).
+    pub fn get_kind(&self) -> &'static str {
+        match self {
+            Self::FileError(_)  => "FileError",
+            Self::ArgError      => "ArgError",
+            Self::ExprError(_)  => "ExprError",
+            Self::RangeError(_) => "RangeError",
+            Self::CellPError(_) => "CellPError",
+            Self::CycleError(_) => "CycleError",
+            Self::TokenError    => "TokenError",
+            Self::RunError(_)   => "RunError",
+            Self::StrictError(_) => "StrictError",
+            Self::CellError(diagnostic) => diagnostic.error.get_kind(),
+            Self::CheckError(_) => "CheckError",
+            Self::ReportedError(err) => err.get_kind(),
+        }
+    }
+
+    /// Returns the message of the error without the "#[X ERROR]" prefix.
+    pub fn get_message(&self) -> String {
+        match self {
+            Self::ArgError   => "Incorrect argument amount...".to_string(),
+            Self::TokenError => "Expression has no tokens...".to_string(),
+            Self::FileError(msg)
+            | Self::ExprError(msg)
+            | Self::RangeError(msg)
+            | Self::CellPError(msg)
+            | Self::CycleError(msg)
+            | Self::RunError(msg) => msg.clone(),
+            Self::StrictError(errors) => format!("{} cell(s) failed to evaluate...", errors.len()),
+            Self::CellError(diagnostic) => diagnostic.error.get_message(),
+            Self::CheckError(problems) => format!("{} problem(s) found...", problems),
+            Self::ReportedError(err) => err.get_message(),
+        }
+    }
+
+    /// Returns the exit code of the command line tool for the error.
+    /// Every variant has its own code, so scripts can tell the failures apart (1 is for sheets that fail `check`).
+    pub fn get_exit_code(&self) -> i32 {
+        match self {
+            Self::RunError(_)   => 2,
+            Self::FileError(_)  => 3,
+            Self::ExprError(_)  => 4,
+            Self::ArgError      => 5,
+            Self::RangeError(_) => 6,
+            Self::CellPError(_) => 7,
+            Self::CycleError(_) => 8,
+            Self::TokenError    => 9,
+            Self::StrictError(_) => 10,
+            Self::CellError(diagnostic) => diagnostic.error.get_exit_code(),
+            Self::CheckError(_) => 1,
+            Self::ReportedError(err) => err.get_exit_code(),
+        }
+    }
 }
 
 impl fmt::Display for CsvError {
@@ -19,7 +87,27 @@ impl fmt::Display for CsvError {
             Self::ExprError(msg)  => write!(f, "#[EXPR ERROR] {}", msg),
             Self::RangeError(msg) => write!(f, "#[RANGE ERROR] {}", msg),
             Self::CellPError(msg) => write!(f, "#[CELL POINTER ERROR] {}", msg),
+            Self::CycleError(msg) => write!(f, "#[CYCLE ERROR] {}", msg),
+            Self::TokenError      => write!(f, "#[TOKEN ERROR]"),
             Self::RunError(msg)   => write!(f, "Error while running application!\n{}", msg),
+            Self::StrictError(errors) => {
+                write!(f, "#[STRICT ERROR] {} cell(s) failed to evaluate:", errors.len())?;
+                // (Located errors are listed by their error only, the cell is already in front of it)
+                errors.iter().try_for_each(|(cell, err)| match err {
+                    Self::CellError(diagnostic) => write!(f, "\n\t{}: {}", cell, diagnostic.error),
+                    _ => write!(f, "\n\t{}: {}", cell, err),
+                })
+            },
+            Self::CellError(diagnostic) => write!(f, "{}", diagnostic),
+            Self::CheckError(problems) => write!(f, "#[CHECK ERROR] {} problem(s) found...", problems),
+            Self::ReportedError(err) => {
+                let message = err.to_string();
+                let first_line = message.lines().next().unwrap_or_default();
+                match first_line.strip_suffix(':') {
+                    Some(summary) => write!(f, "{}...", summary),
+                    None          => write!(f, "{}", first_line),
+                }
+            },
         }
     }
 }
@@ -1,35 +1,18 @@
+mod cli;
+mod repl;
+mod tui;
+mod watch;
+
 use std::env;
 use std::process::exit;
-use echcell::error::CsvError;
-use echcell::csv::{
-    CSV,
-    generate_output,
-};
 
 fn main() {
     // Arguments:
-    let args: Vec<String> = env::args().map(String::from).collect();
-    
-    if args.len() == 1 {
-        eprintln!("\n\t{}\n", CsvError::RunError("No CSV file was given...".to_string()));
-        exit(1);
-    }
-
-    // Creating csv object:
-    let csv = match CSV::new(args[1].clone()) {
-        Ok(val) => val,
-        Err(e)  => {
-            eprintln!("\n\t{e}\n");
-            exit(1);
-        },
-    }; 
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    // Generating output:
-    match generate_output(&csv) {
-        Ok(_) => println!("\n\tOutput file successfully created!\n"),
-        Err(err) => {
-            eprintln!("\n\t{err}\n");
-            exit(1);
-        },
+    // Every failure exits with the code of its CsvError variant:
+    if let Err(err) = cli::parse(&args).and_then(cli::run) {
+        eprintln!("\n\t{err}\n");
+        exit(err.get_exit_code());
     }
 }
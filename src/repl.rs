@@ -0,0 +1,215 @@
+use std::env;
+use std::path::PathBuf;
+use rustyline::{Context, Editor, Helper};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use echcell::csv::{CSV, Value};
+use echcell::error::CsvError;
+
+/// Words of the expression syntax offered by the completion.
+const KEYWORDS: [&str; 6] = ["SUM", "AVG", "CALC", "IF", "THEN", "ELSE"];
+
+/// Commands of the REPL offered by the completion.
+const COMMANDS: [&str; 5] = [":show", ":set", ":save", ":help", ":quit"];
+
+/// Largest amount of cell pointers offered by the completion at once.
+const MAX_CELL_CANDIDATES: usize = 64;
+
+const HELP: &str = "<expression>          evaluates an expression against the sheet (e.g. SUM A1 A5)
+:show <cell>          prints the raw content and the value of a cell
+:set <cell> [item]    overwrites (or clears) a cell and prints every cell whose value changed
+:save                 writes the sheet back to its csv file
+:help                 prints this help
+:quit                 leaves the REPL (Ctrl-D works too)";
+
+/// LINE EDITOR HELPER
+/// Completes function names, REPL commands and the cell pointers of the loaded sheet.
+struct ReplHelper {
+    header: Vec<String>,
+    rows: usize,
+}
+
+impl ReplHelper {
+    /// Returns the words starting with the given prefix.
+    fn get_candidates(&self, word: &str) -> Vec<String> {
+        if word.starts_with(':') {
+            return COMMANDS.iter().filter(|cmd| cmd.starts_with(word)).map(|cmd| cmd.to_string()).collect();
+        }
+
+        let mut candidates: Vec<String> = KEYWORDS.iter()
+            .filter(|keyword| !word.is_empty() && keyword.starts_with(&word.to_uppercase()))
+            .map(|keyword| keyword.to_string())
+            .collect();
+
+        // Cell pointers (only once the column is typed, a whole sheet would be too much):
+        let column = self.header.iter().find(|col| !word.is_empty() && word.starts_with(col.as_str()));
+        if let Some(column) = column {
+            candidates.extend((1..=self.rows)
+                .map(|row| format!("{}{}", column, row))
+                .filter(|cell| cell.starts_with(word))
+                .take(MAX_CELL_CANDIDATES));
+        }
+
+        candidates
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos].rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        Ok((start, self.get_candidates(&line[start..pos])))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+/// Runs an interactive session on a csv file.
+/// Every line is either a command (starting with ':') or an expression that is evaluated against the sheet.
+/// Returns a Result type of () or the CsvError of loading the file or starting the line editor.
+pub fn run(file: &str) -> Result<(), CsvError> {
+    let mut csv = CSV::new(file.to_string())?;
+    csv.recalculate();
+
+    let mut editor: Editor<ReplHelper, DefaultHistory> = match Editor::new() {
+        Ok(editor) => editor,
+        Err(_)     => return Err(CsvError::RunError("Could not start the line editor...".to_string())),
+    };
+    editor.set_helper(Some(ReplHelper { header: csv.header.clone(), rows: csv.get_row_count() }));
+
+    // History is kept between sessions if there is a home directory:
+    let history = env::var_os("HOME").map(|home| PathBuf::from(home).join(".echcell_history"));
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+
+    println!("echcell REPL on {} ({} rows). Type :help for the commands.", csv.file, csv.get_row_count());
+
+    loop {
+        let line = match editor.readline("echcell> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(_) => return Err(CsvError::RunError("Could not read the input line...".to_string())),
+        };
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+
+        if line == ":quit" || line == ":q" {
+            break;
+        }
+
+        match run_line(&mut csv, line) {
+            Ok(output) => println!("{}", output),
+            Err(err)   => println!("{}", err),
+        }
+
+        // The sheet may have grown:
+        if let Some(helper) = editor.helper_mut() {
+            helper.rows = csv.get_row_count();
+        }
+    }
+
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+
+    Ok(())
+}
+
+/// Runs 1 line of the REPL and returns the text to print.
+fn run_line(csv: &mut CSV, line: &str) -> Result<String, CsvError> {
+    let (command, args) = line.split_once(' ').map_or((line, ""), |(command, args)| (command, args.trim()));
+
+    match command {
+        ":help" => Ok(HELP.to_string()),
+        ":save" => {
+            csv.save()?;
+            Ok(format!("Saved to {}", csv.file))
+        },
+        ":show" => {
+            if args.is_empty() || args.contains(' ') {
+                return Err(CsvError::RunError("Usage: :show <cell>".to_string()));
+            }
+
+            let source = csv.get(args)?;
+            Ok(format!("source: {}\nvalue:  {}", source, get_display_value(csv, args)))
+        },
+        ":set" => {
+            if args.is_empty() {
+                return Err(CsvError::RunError("Usage: :set <cell> [item]".to_string()));
+            }
+            // (Without an item the cell is cleared)
+            let (cell_pointer, item) = args.split_once(' ').unwrap_or((args, ""));
+
+            // Printing every cell that was recalculated to a new value:
+            let changed = csv.update(cell_pointer, item.trim())?;
+            let lines: Vec<String> = changed.iter()
+                .map(|cell| format!("{} -> {}", cell, get_display_value(csv, cell)))
+                .collect();
+
+            if lines.is_empty() {
+                Ok("No values changed".to_string())
+            }
+            else {
+                Ok(lines.join("\n"))
+            }
+        },
+        _ if command.starts_with(':') => Err(CsvError::RunError(format!("Unknown command {}; type :help for the commands...", command))),
+        _ => Ok(csv.eval_str(line)?.to_string()),
+    }
+}
+
+/// Returns the value of a cell the way the output file shows it ("#[NULL]" for empty cells, error text for errors).
+fn get_display_value(csv: &CSV, cell_pointer: &str) -> String {
+    match csv.eval_cell(cell_pointer) {
+        Ok(Value::Empty) => "#[NULL]".to_string(),
+        Ok(val)          => val.to_string(),
+        Err(err)         => err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_evaluated_against_the_sheet() {
+        let mut csv = CSV::from_string("repl.csv", "A,B\n2,= CALC A1 * 3\n,\n").unwrap();
+
+        assert_eq!(run_line(&mut csv, "CALC B1 + 1").unwrap(), "7");
+        assert_eq!(run_line(&mut csv, ":show B1").unwrap(), "source: = CALC A1 * 3\nvalue:  6");
+        assert_eq!(run_line(&mut csv, ":set A1 4").unwrap(), "A1 -> 4\nB1 -> 12");
+        assert_eq!(run_line(&mut csv, ":set A2").unwrap(), "No values changed");
+        assert_eq!(run_line(&mut csv, ":show A2").unwrap(), "source: \nvalue:  #[NULL]");
+
+        assert!(matches!(run_line(&mut csv, ":show"), Err(CsvError::RunError(_))));
+        assert!(matches!(run_line(&mut csv, ":drop A1"), Err(CsvError::RunError(_))));
+        assert!(matches!(run_line(&mut csv, ":set Q1 1"), Err(CsvError::CellPError(_))));
+    }
+
+    #[test]
+    fn words_are_completed() {
+        let helper = ReplHelper { header: vec!["A".to_string(), "B".to_string()], rows: 12 };
+        assert_eq!(helper.get_candidates(":s"), vec![":show", ":set", ":save"]);
+        assert_eq!(helper.get_candidates("a"), vec!["AVG"]);
+        assert_eq!(helper.get_candidates("B1"), vec!["B1", "B10", "B11", "B12"]);
+        assert!(helper.get_candidates("").is_empty());
+    }
+}
@@ -0,0 +1,368 @@
+use std::io::{self, Stdout, Write};
+use crossterm::{cursor, execute, queue, terminal};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use echcell::csv::{CSV, Value};
+use echcell::error::CsvError;
+
+/// Width of every column of the grid (values are cut to fit).
+const COLUMN_WIDTH: usize = 12;
+
+/// Width of the row number gutter.
+const GUTTER_WIDTH: usize = 6;
+
+/// Lines above the grid (title, edit bar and column names) and below it (status line).
+const TOP_LINES: usize = 3;
+const BOTTOM_LINES: usize = 1;
+
+const HELP: &str = "arrows move | Enter edit | Del clear | s save | q quit";
+
+/// Whether keys move the selection or edit the selected cell.
+enum Mode {
+    Normal,
+    Editing { buffer: Vec<char>, pos: usize },
+}
+
+/// TERMINAL VIEWER STATE
+/// The sheet, the selected cell, the scroll position and the line shown in the status bar.
+/// Values are evaluated when they are drawn, edits go through `CSV::update` so only the dependents are recalculated.
+struct Viewer {
+    csv: CSV,
+    x: usize,
+    y: usize,
+    left: usize,
+    top: usize,
+    mode: Mode,
+    status: String,
+    modified: bool,
+    quit_pending: bool,
+}
+
+/// Puts the terminal back into its normal state when the viewer is left (also when it fails).
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+        let _ = execute!(io::stdout(), terminal::LeaveAlternateScreen, cursor::Show);
+    }
+}
+
+/// Opens a csv file in a scrollable grid that shows the evaluated values.
+/// The raw content of the selected cell is shown in the edit bar; edits are recalculated right away and `s` saves the sheet.
+/// Returns a Result type of () or the CsvError of loading the file or drawing the terminal.
+pub fn run(file: &str) -> Result<(), CsvError> {
+    let mut csv = CSV::new(file.to_string())?;
+    csv.recalculate();
+
+    let mut viewer = Viewer {
+        csv,
+        x: 0,
+        y: 0,
+        left: 0,
+        top: 0,
+        mode: Mode::Normal,
+        status: HELP.to_string(),
+        modified: false,
+        quit_pending: false,
+    };
+
+    let mut stdout = io::stdout();
+    if terminal::enable_raw_mode().is_err() {
+        return Err(CsvError::RunError("Could not set up the terminal...".to_string()));
+    }
+    let _guard = TerminalGuard;
+    check(execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide))?;
+
+    loop {
+        let (width, height) = check(terminal::size())?;
+        viewer.scroll(width as usize, height as usize);
+        check(viewer.draw(&mut stdout, width as usize, height as usize))?;
+
+        match check(event::read())? {
+            Event::Key(key) if key.kind != KeyEventKind::Release && !viewer.handle_key(key, height as usize) => break,
+            _ => {},
+        }
+    }
+
+    Ok(())
+}
+
+impl Viewer {
+    /// Handles a key press; returns false once the viewer should be left.
+    fn handle_key(&mut self, key: KeyEvent, height: usize) -> bool {
+        let page = height.saturating_sub(TOP_LINES + BOTTOM_LINES).max(1);
+
+        match &mut self.mode {
+            Mode::Editing { buffer, pos } => match key.code {
+                KeyCode::Enter => {
+                    let item: String = buffer.iter().collect();
+                    self.mode = Mode::Normal;
+                    self.set_selected(&item);
+                },
+                KeyCode::Esc       => {
+                    self.mode = Mode::Normal;
+                    self.status = HELP.to_string();
+                },
+                KeyCode::Left      => *pos = pos.saturating_sub(1),
+                KeyCode::Right     => *pos = (*pos + 1).min(buffer.len()),
+                KeyCode::Home      => *pos = 0,
+                KeyCode::End       => *pos = buffer.len(),
+                KeyCode::Backspace if *pos > 0 => {
+                    *pos -= 1;
+                    buffer.remove(*pos);
+                },
+                KeyCode::Delete if *pos < buffer.len() => {
+                    buffer.remove(*pos);
+                },
+                KeyCode::Char(c)   => {
+                    buffer.insert(*pos, c);
+                    *pos += 1;
+                },
+                _ => {},
+            },
+            Mode::Normal => {
+                // Leaving with unsaved edits needs a second `q`:
+                if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                    if !self.modified || self.quit_pending {
+                        return false;
+                    }
+                    self.quit_pending = true;
+                    self.status = "Unsaved changes! Press q again to quit without saving or s to save".to_string();
+                    return true;
+                }
+                self.quit_pending = false;
+
+                // (The row after the last one can be selected to add a row)
+                let last_row = self.csv.get_row_count();
+                let last_col = self.csv.header.len().saturating_sub(1);
+
+                match key.code {
+                    KeyCode::Up    | KeyCode::Char('k') => self.y = self.y.saturating_sub(1),
+                    KeyCode::Down  | KeyCode::Char('j') => self.y = (self.y + 1).min(last_row),
+                    KeyCode::Left  | KeyCode::Char('h') => self.x = self.x.saturating_sub(1),
+                    KeyCode::Right | KeyCode::Char('l') => self.x = (self.x + 1).min(last_col),
+                    KeyCode::PageUp   => self.y = self.y.saturating_sub(page),
+                    KeyCode::PageDown => self.y = (self.y + page).min(last_row),
+                    KeyCode::Home     => self.x = 0,
+                    KeyCode::End      => self.x = last_col,
+                    KeyCode::Enter | KeyCode::F(2) => {
+                        let buffer: Vec<char> = self.csv.get(&self.get_selected()).unwrap_or_default().chars().collect();
+                        let pos = buffer.len();
+                        self.mode = Mode::Editing { buffer, pos };
+                        self.status = "Enter save the cell | Esc cancel".to_string();
+                    },
+                    KeyCode::Delete | KeyCode::Backspace => self.set_selected(""),
+                    KeyCode::Char('s') => self.save(),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+                    _ => {},
+                }
+            },
+        }
+
+        true
+    }
+
+    /// Overwrites the selected cell and reports how many values changed.
+    fn set_selected(&mut self, item: &str) {
+        let cell_pointer = self.get_selected();
+
+        self.status = match self.csv.update(&cell_pointer, item) {
+            Ok(changed) => {
+                self.modified = true;
+                format!("{} updated, {} value(s) changed", cell_pointer, changed.len())
+            },
+            Err(err) => err.to_string(),
+        };
+    }
+
+    /// Writes the sheet back to its csv file.
+    fn save(&mut self) {
+        self.status = match self.csv.save() {
+            Ok(_) => {
+                self.modified = false;
+                format!("Saved to {}", self.csv.file)
+            },
+            Err(err) => err.to_string(),
+        };
+    }
+
+    /// Returns the pointer of the selected cell.
+    fn get_selected(&self) -> String {
+        format!("{}{}", self.csv.header[self.x], self.y + 1)
+    }
+
+    /// Moves the visible part of the grid so the selected cell is on the screen.
+    fn scroll(&mut self, width: usize, height: usize) {
+        let rows = height.saturating_sub(TOP_LINES + BOTTOM_LINES).max(1);
+        let cols = (width.saturating_sub(GUTTER_WIDTH) / COLUMN_WIDTH).max(1);
+
+        if self.y < self.top {
+            self.top = self.y;
+        }
+        else if self.y >= self.top + rows {
+            self.top = self.y + 1 - rows;
+        }
+
+        if self.x < self.left {
+            self.left = self.x;
+        }
+        else if self.x >= self.left + cols {
+            self.left = self.x + 1 - cols;
+        }
+    }
+
+    /// Draws the whole screen.
+    fn draw(&self, stdout: &mut Stdout, width: usize, height: usize) -> io::Result<()> {
+        let rows = height.saturating_sub(TOP_LINES + BOTTOM_LINES);
+        let cols = (width.saturating_sub(GUTTER_WIDTH) / COLUMN_WIDTH).max(1);
+        let visible_cols = self.left..(self.left + cols).min(self.csv.header.len());
+
+        // Title:
+        let title = format!("echcell - {}{}", self.csv.file, if self.modified { " [modified]" } else { "" });
+        draw_line(stdout, 0, &title, width, true)?;
+
+        // Edit bar:
+        let cell_pointer = self.get_selected();
+        let bar = match &self.mode {
+            Mode::Normal => format!("{:<w$}| {}", cell_pointer, self.csv.get(&cell_pointer).unwrap_or_default(), w = GUTTER_WIDTH - 1),
+            Mode::Editing { buffer, .. } => format!("{:<w$}> {}", cell_pointer, buffer.iter().collect::<String>(), w = GUTTER_WIDTH - 1),
+        };
+        draw_line(stdout, 1, &bar, width, false)?;
+
+        // Column names:
+        let names: String = visible_cols.clone().map(|x| fit(&self.csv.header[x], COLUMN_WIDTH)).collect();
+        draw_line(stdout, 2, &format!("{}{}", " ".repeat(GUTTER_WIDTH), names), width, true)?;
+
+        // Rows:
+        for i in 0..rows {
+            let y = self.top + i;
+            let line = (TOP_LINES + i) as u16;
+            queue!(stdout, cursor::MoveTo(0, line), terminal::Clear(terminal::ClearType::CurrentLine))?;
+            if y > self.csv.get_row_count() {
+                continue;
+            }
+
+            queue!(stdout, Print(fit(&(y + 1).to_string(), GUTTER_WIDTH)))?;
+            for x in visible_cols.clone() {
+                let value = fit(&self.get_value(x, y), COLUMN_WIDTH);
+                if x == self.x && y == self.y {
+                    queue!(stdout, SetAttribute(Attribute::Reverse), Print(value), SetAttribute(Attribute::Reset))?;
+                }
+                else {
+                    queue!(stdout, Print(value))?;
+                }
+            }
+        }
+
+        // Status line:
+        draw_line(stdout, height.saturating_sub(1) as u16, &self.status, width, false)?;
+
+        // Showing the text cursor only while editing:
+        match &self.mode {
+            Mode::Editing { pos, .. } => queue!(stdout, cursor::MoveTo((GUTTER_WIDTH + 1 + pos) as u16, 1), cursor::Show)?,
+            Mode::Normal              => queue!(stdout, cursor::Hide)?,
+        }
+
+        stdout.flush()
+    }
+
+    /// Returns the evaluated value of a cell as it is shown in the grid (empty cells are blank, errors show their text).
+    fn get_value(&self, x: usize, y: usize) -> String {
+        if y >= self.csv.get_row_count() {
+            return String::new();
+        }
+
+        match self.csv.eval_cell(&format!("{}{}", self.csv.header[x], y + 1)) {
+            Ok(Value::Empty) => String::new(),
+            Ok(val)          => val.to_string(),
+            Err(err)         => err.to_string(),
+        }
+    }
+}
+
+/// Draws a whole line of the screen, optionally highlighted.
+fn draw_line(stdout: &mut Stdout, line: u16, text: &str, width: usize, highlight: bool) -> io::Result<()> {
+    queue!(stdout, cursor::MoveTo(0, line), terminal::Clear(terminal::ClearType::CurrentLine))?;
+
+    let text = fit(text, width);
+    if highlight {
+        queue!(stdout, SetAttribute(Attribute::Reverse), Print(text), SetAttribute(Attribute::Reset))
+    }
+    else {
+        queue!(stdout, Print(text))
+    }
+}
+
+/// Cuts or pads a text to the given width (1 column is left blank as a separator).
+fn fit(text: &str, width: usize) -> String {
+    let width = width.saturating_sub(1);
+    let text: String = text.chars().map(|c| if c == '\n' { ' ' } else { c }).take(width).collect();
+    format!("{:<w$} ", text, w = width)
+}
+
+/// Converts a terminal error into a CsvError::RunError().
+fn check<T>(result: io::Result<T>) -> Result<T, CsvError> {
+    match result {
+        Ok(val) => Ok(val),
+        Err(_)  => Err(CsvError::RunError("Could not draw the terminal...".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a viewer of a sheet in memory.
+    fn viewer(source: &str) -> Viewer {
+        let mut csv = CSV::from_string("tui.csv", source).unwrap();
+        csv.recalculate();
+        Viewer { csv, x: 0, y: 0, left: 0, top: 0, mode: Mode::Normal, status: HELP.to_string(), modified: false, quit_pending: false }
+    }
+
+    /// Presses the given keys one after the other.
+    fn type_keys(viewer: &mut Viewer, keys: &[KeyCode]) {
+        keys.iter().for_each(|code| {
+            viewer.handle_key(KeyEvent::new(*code, KeyModifiers::NONE), 24);
+        });
+    }
+
+    #[test]
+    fn cells_are_edited_in_place() {
+        let mut viewer = viewer("A,B\n2,= CALC A1 * 2\n");
+        type_keys(&mut viewer, &[KeyCode::Enter, KeyCode::Backspace, KeyCode::Char('5'), KeyCode::Enter]);
+        assert_eq!(viewer.csv.get("A1").unwrap(), "5");
+        assert_eq!(viewer.get_value(1, 0), "10");
+        assert_eq!(viewer.status, "A1 updated, 2 value(s) changed");
+
+        // (Items the csv file can't hold are refused)
+        type_keys(&mut viewer, &[KeyCode::Enter, KeyCode::Char(','), KeyCode::Char('1'), KeyCode::Enter]);
+        assert_eq!(viewer.csv.get("A1").unwrap(), "5");
+        assert!(viewer.status.contains("commas"));
+
+        // Rows are added below the last one:
+        type_keys(&mut viewer, &[KeyCode::Down, KeyCode::Down, KeyCode::Down, KeyCode::Enter, KeyCode::Char('x'), KeyCode::Enter]);
+        assert_eq!(viewer.csv.get_row_count(), 2);
+        assert_eq!(viewer.get_value(0, 1), "x");
+    }
+
+    #[test]
+    fn unsaved_edits_need_a_second_quit() {
+        let mut viewer = viewer("A,B\n1,2\n");
+        assert!(!viewer.handle_key(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE), 24));
+
+        type_keys(&mut viewer, &[KeyCode::Delete]);
+        assert!(viewer.handle_key(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE), 24));
+        assert!(!viewer.handle_key(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE), 24));
+    }
+
+    #[test]
+    fn the_selected_cell_stays_visible() {
+        let mut viewer = viewer(&format!("A,B,C,D,E,F,G,H\n{}", "1,2,3,4,5,6,7,8\n".repeat(100)));
+        type_keys(&mut viewer, &[KeyCode::End, KeyCode::PageDown, KeyCode::PageDown]);
+        viewer.scroll(40, 24);
+        assert_eq!((viewer.x, viewer.y), (7, 40));
+        assert_eq!((viewer.left, viewer.top), (6, 21));
+        assert_eq!(fit("a\nb", 5), "a b  ");
+    }
+}
@@ -0,0 +1,148 @@
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use echcell::csv::{CSV, generate_output};
+use echcell::error::CsvError;
+
+/// Time between 2 checks of the watched files.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Time the watched files have to stay unchanged before the sheet is evaluated again
+/// (editors often write a file in several steps).
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Largest amount of changed cells listed after an evaluation.
+const MAX_LISTED_CELLS: usize = 20;
+
+/// Modification time and size of every watched file (None if a file can't be read).
+type Snapshot = Vec<Option<(SystemTime, u64)>>;
+
+/// Evaluates a csv file and regenerates its output file every time it or one of its linked files changes.
+/// After every evaluation the cells whose value changed (or that fail now) are listed.
+/// Runs until the process is stopped; failing evaluations are reported and watching goes on.
+pub fn run(file: &str) {
+    let mut watched: Vec<PathBuf> = vec![PathBuf::from(file)];
+    let mut previous: Option<Vec<Vec<String>>> = None;
+
+    loop {
+        // Evaluating the sheet and listing the changes:
+        match evaluate(file) {
+            Ok((csv, rows)) => {
+                match &previous {
+                    Some(previous) => print_changes(&csv, previous, &rows),
+                    None => println!("\n\tWatching {} and its linked files ({} rows evaluated). Press Ctrl-C to stop.", csv.file, rows.len()),
+                }
+
+                watched = [PathBuf::from(file)].into_iter().chain(csv.get_linked_files()).collect();
+                previous = Some(rows);
+            },
+            Err(err) => println!("\n\t{err}"),
+        }
+
+        // Waiting for a change that isn't followed by another one within `DEBOUNCE`:
+        let mut snapshot = get_snapshot(&watched);
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            if get_snapshot(&watched) != snapshot {
+                break;
+            }
+        }
+        loop {
+            snapshot = get_snapshot(&watched);
+            thread::sleep(DEBOUNCE);
+            if get_snapshot(&watched) == snapshot {
+                break;
+            }
+        }
+    }
+}
+
+/// Loads and evaluates a csv file, creates its output file and returns the sheet with its output rows.
+fn evaluate(file: &str) -> Result<(CSV, Vec<Vec<String>>), CsvError> {
+    let mut csv = CSV::new(file.to_string())?;
+    csv.recalculate();
+    generate_output(&csv)?;
+
+    let rows = (0..csv.get_row_count()).map(|y| csv.get_output_row(y)).collect();
+    Ok((csv, rows))
+}
+
+/// Prints the cells whose output changed since the previous evaluation.
+fn print_changes(csv: &CSV, previous: &[Vec<String>], rows: &[Vec<String>]) {
+    let (changed, failing) = get_changes(csv, previous, rows);
+    println!("\n\t{} evaluated again: {} cell(s) changed, {} of them failing", csv.file, changed.len(), failing);
+
+    changed.iter().take(MAX_LISTED_CELLS).for_each(|(cell, old, new)| {
+        println!("\t  {}: {} -> {}", cell, old, new);
+    });
+    if changed.len() > MAX_LISTED_CELLS {
+        println!("\t  ... and {} more", changed.len() - MAX_LISTED_CELLS);
+    }
+}
+
+/// Compares the output rows of 2 evaluations.
+/// Returns the changed cells (cell pointer, old and new output) and the amount of them that fail now.
+fn get_changes<'a>(csv: &CSV, previous: &'a [Vec<String>], rows: &'a [Vec<String>]) -> (Vec<(String, &'a str, &'a str)>, usize) {
+    let mut changed: Vec<(String, &str, &str)> = Vec::new();
+    let mut failing = 0;
+
+    for y in 0..previous.len().max(rows.len()) {
+        let old_row = previous.get(y).map(Vec::as_slice).unwrap_or(&[]);
+        let new_row = rows.get(y).map(Vec::as_slice).unwrap_or(&[]);
+
+        for x in 0..old_row.len().max(new_row.len()).min(csv.header.len()) {
+            let old = old_row.get(x).map(String::as_str).unwrap_or("#[NULL]");
+            let new = new_row.get(x).map(String::as_str).unwrap_or("#[NULL]");
+            if old != new {
+                let cell = format!("{}{}", csv.header[x], y + 1);
+                // (Cells that don't exist anymore never count as failing)
+                if new_row.get(x).is_some() && csv.eval_cell(&cell).is_err() {
+                    failing += 1;
+                }
+                changed.push((cell, old, new));
+            }
+        }
+    }
+
+    (changed, failing)
+}
+
+/// Returns the modification times and sizes of the given files.
+fn get_snapshot(paths: &[PathBuf]) -> Snapshot {
+    paths.iter()
+        .map(|path| {
+            let metadata = fs::metadata(path).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use echcell::csv::get_output_path;
+
+    #[test]
+    fn changed_cells_are_listed() {
+        let path = std::env::temp_dir().join(format!("echcell_{}_watch.csv", std::process::id()));
+        let file = path.display().to_string();
+
+        fs::write(&path, "A,B\n1,= CALC A1 * 2\n2,3\n").unwrap();
+        let (_, previous) = evaluate(&file).unwrap();
+        let snapshot = get_snapshot(std::slice::from_ref(&path));
+
+        fs::write(&path, "A,B\nx,= CALC A1 * 2\n2,3\n4\n").unwrap();
+        let (csv, rows) = evaluate(&file).unwrap();
+        assert_ne!(get_snapshot(std::slice::from_ref(&path)), snapshot);
+
+        let (changed, failing) = get_changes(&csv, &previous, &rows);
+        let cells: Vec<&str> = changed.iter().map(|(cell, _, _)| cell.as_str()).collect();
+        assert_eq!(cells, vec!["A1", "B1", "A3"]);
+        assert_eq!((changed[2].1, changed[2].2), ("#[NULL]", "4"));
+        assert_eq!(failing, 1);
+
+        fs::remove_file(get_output_path(&file)).unwrap();
+        fs::remove_file(path).unwrap();
+    }
+}